@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::{Block, circuit_voxel, CustomState, Signal, terminal_name, VoxelID};
+use crate::grid::Coord;
+use crate::registry::{BlockKind, Context};
+
+/// Layout of a custom block: its solid voxels and its terminals by name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CustomLayout
+{
+    #[serde(default)]
+    pub solids: Vec<Coord>,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, Coord>,
+    #[serde(default)]
+    pub outputs: BTreeMap<String, Coord>,
+}
+
+/// Logic of a custom block kind, placed as `Block::Custom` (see `Scene::register_behavior`)
+///
+/// Can be implemented outside of this crate, e.g. for lab-specific components
+pub trait BlockBehavior: Send + Sync
+{
+    /// Get the layout of blocks of this kind
+    fn layout(&self) -> CustomLayout;
+
+    /// Compute the block's new state and its output signals (by terminal name),
+    /// given its current state and the signal present at each input terminal (by terminal name) prior to this tick
+    fn evaluate(&self, state: &CustomState, inputs: &CustomState, context: &Context) -> (CustomState, CustomState);
+}
+
+/// Block kind implemented by a `BlockBehavior`
+///
+/// The block's state and last outputs are stored in its `VoxelCustom` data so that they are saved with the world
+pub struct BehaviorKind(pub Arc<dyn BlockBehavior>);
+
+impl BlockKind for BehaviorKind
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        let layout = self.0.layout();
+
+        layout.solids
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (format!("solid-{}", i), *e))
+            .chain(layout.inputs
+                .iter()
+                .chain(layout.outputs.iter())
+                .map(|(name, e)| (circuit_voxel(name), *e)))
+            .collect()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        let Block::Custom(data) = block else { return Default::default() };
+
+        self.0.layout().outputs
+            .keys()
+            .map(|name| (circuit_voxel(name), data.outputs.get(name).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        let Block::Custom(data) = block else { return };
+
+        let inputs = inputs
+            .iter()
+            .filter_map(|(voxel_id, signal)| terminal_name(voxel_id).map(|e| (e.to_string(), *signal)))
+            .collect();
+
+        (data.state, data.outputs) = self.0.evaluate(&data.state, &inputs, context);
+    }
+}
+
+#[test]
+fn behavior_test()
+{
+    use crate::block::{VoxelCustom, VoxelPowered, VoxelWire};
+    use crate::scene::{Scene, SceneData};
+
+    /// Counts the rising edges of its input, outputting whether the count is odd
+    struct EdgeCounter;
+
+    impl BlockBehavior for EdgeCounter
+    {
+        fn layout(&self) -> CustomLayout {
+            CustomLayout {
+                solids: vec![],
+                inputs: [("in".to_string(), Coord::new(0, 0, 0))].into(),
+                outputs: [("odd".to_string(), Coord::new(0, 0, 1))].into(),
+            }
+        }
+
+        fn evaluate(&self, state: &CustomState, inputs: &CustomState, _context: &Context) -> (CustomState, CustomState) {
+            let (input, last) = (inputs["in"], state.get("last").copied().unwrap_or(0));
+            let count = state.get("count").copied().unwrap_or(0) + Signal::from(input != 0 && last == 0);
+
+            ([("count".to_string(), count), ("last".to_string(), input)].into(), [("odd".to_string(), count % 2)].into())
+        }
+    }
+
+    Scene::register_behavior("test_edge_counter", EdgeCounter).unwrap();
+    assert!(Scene::register_behavior("test_edge_counter", EdgeCounter).is_err());
+
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    let counter = scene.add_block(Block::Custom(VoxelCustom {
+        kind: "test_edge_counter".to_string(),
+        ..Default::default()
+    }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(0, 0, 2), Default::default()).unwrap();
+
+    scene.simulate_tick();
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: true, ..Default::default() }));
+
+    // Custom blocks survive a save/load round trip and keep simulating
+    let data = serde_json::to_string(&SceneData::from(scene.clone())).unwrap();
+    let mut loaded = Scene::from(serde_json::from_str::<SceneData>(&data).unwrap());
+    assert_eq!(loaded.get_world_blocks(), scene.get_world_blocks());
+
+    loaded.update_block(toggle, Block::Toggle(VoxelPowered { powered: false }));
+    loaded.simulate_tick();
+    loaded.update_block(toggle, Block::Toggle(VoxelPowered { powered: true }));
+    loaded.simulate_tick();
+    assert_eq!(loaded.get_block(counter).unwrap().2, Block::Custom(VoxelCustom {
+        kind: "test_edge_counter".to_string(),
+        state: [("count".to_string(), 2), ("last".to_string(), 1)].into(),
+        outputs: [("odd".to_string(), 0)].into(),
+    }));
+    assert_eq!(loaded.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: false, ..Default::default() }));
+}
@@ -1,17 +1,50 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
-use cgmath::Zero;
 use serde::{Deserialize, Serialize};
 
 use crate::grid::Coord;
+use crate::registry::{BlockKind, get_kind};
+use crate::scene::Scene;
 
 pub type VoxelID = String;
 pub type PowerState = bool;
-pub type _Color = (u8, u8, u8, u8);
+pub type Signal = u64;
+pub type CustomState = BTreeMap<String, Signal>;
+/// RGBA color
+pub type Color = (u8, u8, u8, u8);
 
 const VOXEL_CIRCUIT: &str = "!";
 
+/// Widest value a bus can carry
+pub const MAX_BUS_WIDTH: u8 = 64;
+
+/// Strength of a wire next to a powered source in an analog scene (see `Scene::set_analog`)
+pub const MAX_STRENGTH: Signal = 15;
+
+/// Most words a ROM or RAM block can store
+pub const MAX_MEMORY_DEPTH: u32 = 1 << 16;
+
+/// Longest delay of a repeater, in ticks
+pub const MAX_REPEATER_DELAY: u32 = 1 << 10;
+
+/// Fewest inputs a logic gate can have
+pub const MIN_GATE_INPUTS: u8 = 2;
+/// Most inputs a logic gate can have
+pub const MAX_GATE_INPUTS: u8 = 8;
+
+/// Fewest select lines a multiplexer, demultiplexer, encoder or decoder can have
+pub const MIN_SELECT_BITS: u8 = 1;
+/// Most select lines a multiplexer, demultiplexer, encoder or decoder can have
+pub const MAX_SELECT_BITS: u8 = 3;
+
+/// Fewest inputs a color pixel driven by several inputs can have
+pub const MIN_COLOR_INPUTS: u8 = 3;
+
+pub const BLACK: Color = (0, 0, 0, 255);
+pub const WHITE: Color = (255, 255, 255, 255);
+
 pub fn circuit_voxel(name: &str) -> String
 {
     format!("{}{}", VOXEL_CIRCUIT, name)
@@ -22,10 +55,40 @@ pub fn is_circuit_voxel(name: &str) -> bool
     name.starts_with(VOXEL_CIRCUIT)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
-pub enum Orient
+/// Get the terminal name of a circuit voxel (returns value only if the voxel is a circuit voxel)
+pub fn terminal_name(name: &str) -> Option<&str>
+{
+    name.strip_prefix(VOXEL_CIRCUIT)
+}
+
+/// Name of the circuit voxel with the given index in a numbered group of terminals (e.g. `in_0`, `in_1`, ...)
+pub fn indexed_voxel(name: &str, index: u8) -> String
+{
+    circuit_voxel(&format!("{}_{}", name, index))
+}
+
+/// Name of the circuit voxel carrying the given bit of a bus splitter/merger
+pub fn bit_voxel(bit: u8) -> String
+{
+    indexed_voxel("bit", bit)
+}
+
+/// Get the mask of the bits a bus of the given width can carry (width is clamped to 1-64)
+pub fn bus_mask(width: u8) -> Signal
+{
+    match width.clamp(1, MAX_BUS_WIDTH) {
+        MAX_BUS_WIDTH => Signal::MAX,
+        w => (1 << w) - 1,
+    }
+}
+
+/// Direction a block's front (local +Z) faces
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Facing
 {
     /// Facing +Z
+    #[default]
     FORWARD,
     /// Facing +X
     RIGHT,
@@ -39,10 +102,183 @@ pub enum Orient
     DOWNWARD,
 }
 
-impl Default for Orient
+impl Facing
 {
-    fn default() -> Self {
-        Self::FORWARD
+    pub const ALL: [Facing; 6] = [Facing::FORWARD, Facing::RIGHT, Facing::LEFT, Facing::BACKWARD, Facing::UPWARD, Facing::DOWNWARD];
+}
+
+/// Axis across which a block can be mirrored
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Axis
+{
+    #[serde(rename = "x")]
+    X,
+    #[serde(rename = "y")]
+    Y,
+    #[serde(rename = "z")]
+    Z,
+}
+
+/// Integer transformation matrix (row-major)
+pub type Rotation = [[i32; 3]; 3];
+
+/// Multiply two transformation matrices
+fn multiply(a: Rotation, b: Rotation) -> Rotation {
+    let mut result = [[0; 3]; 3];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, e) in row.iter_mut().enumerate() {
+            *e = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    result
+}
+
+/// Placement of a block: one of the 24 rotations of a cube, optionally mirrored
+///
+/// The block is first mirrored across its local `mirror` axis (if any), then rolled by `roll` counterclockwise
+/// quarter turns about its local +Z axis, then turned so that its local +Z axis points towards `facing`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "OrientData", into = "OrientData")]
+pub struct Orient
+{
+    pub facing: Facing,
+    /// Quarter turns about the block's local +Z axis (0-3)
+    pub roll: u8,
+    pub mirror: Option<Axis>,
+}
+
+/// Orientation storage type for serialization/deserialization
+///
+/// Orientations without roll or mirroring are stored as a bare facing (as in the original protocol)
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+enum OrientData
+{
+    Facing(Facing),
+    Rolled {
+        facing: Facing,
+        #[serde(default)]
+        roll: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mirror: Option<Axis>,
+    },
+}
+
+impl From<OrientData> for Orient
+{
+    fn from(value: OrientData) -> Self {
+        match value {
+            OrientData::Facing(facing) => Orient::new(facing, 0),
+            OrientData::Rolled { facing, roll, mirror } => Orient::new(facing, roll).with_mirror(mirror),
+        }
+    }
+}
+
+impl From<Orient> for OrientData
+{
+    fn from(value: Orient) -> Self {
+        match (value.roll, value.mirror) {
+            (0, None) => OrientData::Facing(value.facing),
+            (roll, mirror) => OrientData::Rolled { facing: value.facing, roll, mirror },
+        }
+    }
+}
+
+impl Orient
+{
+    pub const FORWARD: Orient = Orient { facing: Facing::FORWARD, roll: 0, mirror: None };
+    pub const RIGHT: Orient = Orient { facing: Facing::RIGHT, roll: 0, mirror: None };
+    pub const LEFT: Orient = Orient { facing: Facing::LEFT, roll: 0, mirror: None };
+    pub const BACKWARD: Orient = Orient { facing: Facing::BACKWARD, roll: 0, mirror: None };
+    pub const UPWARD: Orient = Orient { facing: Facing::UPWARD, roll: 0, mirror: None };
+    pub const DOWNWARD: Orient = Orient { facing: Facing::DOWNWARD, roll: 0, mirror: None };
+
+    pub fn new(facing: Facing, roll: u8) -> Self {
+        Self {
+            facing,
+            roll: roll % 4,
+            mirror: None,
+        }
+    }
+
+    /// Get this orientation mirrored across the given local axis (instead of its current mirror axis)
+    pub fn with_mirror(self, mirror: Option<Axis>) -> Self {
+        Self {
+            mirror,
+            ..self
+        }
+    }
+
+    /// Get all 24 orientations (without mirroring)
+    pub fn all() -> impl Iterator<Item=Orient> {
+        Facing::ALL
+            .into_iter()
+            .flat_map(|facing| (0..4).map(move |roll| Orient::new(facing, roll)))
+    }
+
+    /// Get the orientation with the given transformation matrix (if it is a rotation, optionally mirrored)
+    pub fn from_matrix(matrix: Rotation) -> Option<Orient> {
+        Orient::all()
+            .flat_map(|e| [None, Some(Axis::X), Some(Axis::Y), Some(Axis::Z)].map(|m| e.with_mirror(m)))
+            .find(|e| e.matrix() == matrix)
+    }
+
+    /// Get the orientation reproducing the layout of a block saved with the given facing before orientations
+    /// were rotations, when turning a block swapped two of its axes (mirroring it unless it faced backward)
+    pub fn from_legacy(facing: Facing) -> Orient {
+        let matrix = match facing {
+            Facing::FORWARD => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            Facing::RIGHT => [[0, 0, 1], [0, 1, 0], [1, 0, 0]],
+            Facing::LEFT => [[0, 0, -1], [0, 1, 0], [-1, 0, 0]],
+            Facing::BACKWARD => [[-1, 0, 0], [0, 1, 0], [0, 0, -1]],
+            Facing::UPWARD => [[1, 0, 0], [0, 0, 1], [0, 1, 0]],
+            Facing::DOWNWARD => [[1, 0, 0], [0, 0, -1], [0, -1, 0]],
+        };
+
+        Orient::from_matrix(matrix).unwrap()
+    }
+
+    /// Get the orientation of a block placed with this orientation inside a region placed with the `outer` orientation
+    pub fn within(&self, outer: Orient) -> Orient {
+        Orient::from_matrix(multiply(outer.matrix(), self.matrix())).unwrap()
+    }
+
+    /// Get the transformation matrix of this orientation
+    pub fn matrix(&self) -> Rotation {
+        let facing = match self.facing {
+            Facing::FORWARD => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            Facing::RIGHT => [[0, 0, 1], [0, 1, 0], [-1, 0, 0]],
+            Facing::LEFT => [[0, 0, -1], [0, 1, 0], [1, 0, 0]],
+            Facing::BACKWARD => [[-1, 0, 0], [0, 1, 0], [0, 0, -1]],
+            Facing::UPWARD => [[1, 0, 0], [0, 0, 1], [0, -1, 0]],
+            Facing::DOWNWARD => [[1, 0, 0], [0, 0, -1], [0, 1, 0]],
+        };
+        let roll = match self.roll % 4 {
+            0 => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            1 => [[0, -1, 0], [1, 0, 0], [0, 0, 1]],
+            2 => [[-1, 0, 0], [0, -1, 0], [0, 0, 1]],
+            _ => [[0, 1, 0], [-1, 0, 0], [0, 0, 1]],
+        };
+        let mirror = match self.mirror {
+            None => [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            Some(Axis::X) => [[-1, 0, 0], [0, 1, 0], [0, 0, 1]],
+            Some(Axis::Y) => [[1, 0, 0], [0, -1, 0], [0, 0, 1]],
+            Some(Axis::Z) => [[1, 0, 0], [0, 1, 0], [0, 0, -1]],
+        };
+
+        // Mirror first, then roll, then face
+        multiply(facing, multiply(roll, mirror))
+    }
+
+    /// Transform the given block-local coordinate
+    pub fn rotate(&self, coord: Coord) -> Coord {
+        let m = self.matrix();
+
+        Coord::new(
+            m[0][0] * coord.x + m[0][1] * coord.y + m[0][2] * coord.z,
+            m[1][0] * coord.x + m[1][1] * coord.y + m[1][2] * coord.z,
+            m[2][0] * coord.x + m[2][1] * coord.y + m[2][2] * coord.z,
+        )
     }
 }
 
@@ -52,6 +288,97 @@ pub struct VoxelPowered
     pub powered: bool,
 }
 
+fn default_gate_inputs() -> u8
+{
+    MIN_GATE_INPUTS
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoxelGate
+{
+    /// Number of inputs (2-8)
+    #[serde(default = "default_gate_inputs")]
+    pub inputs: u8,
+    pub powered: bool,
+}
+
+impl Default for VoxelGate
+{
+    fn default() -> Self {
+        Self {
+            inputs: MIN_GATE_INPUTS,
+            powered: false,
+        }
+    }
+}
+
+/// Truth table of a lookup table block, indexed by its input states (with `in_0` as the least significant bit)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TruthTable
+{
+    /// Output for each index as a string of `0`s and `1`s, starting from index 0
+    Bits(String),
+    /// Indices for which the output is powered
+    Minterms(Vec<u32>),
+}
+
+impl Default for TruthTable
+{
+    fn default() -> Self {
+        TruthTable::Minterms(vec![])
+    }
+}
+
+impl TruthTable
+{
+    /// Get the output for the given index (unpowered if the table does not list it)
+    pub fn lookup(&self, index: u32) -> PowerState {
+        match self {
+            TruthTable::Bits(bits) => bits.chars().nth(index as usize) == Some('1'),
+            TruthTable::Minterms(minterms) => minterms.contains(&index),
+        }
+    }
+    /// Check that the table describes exactly the given number of entries
+    pub fn validate(&self, entries: u32) -> Result<(), String> {
+        match self {
+            TruthTable::Bits(bits) if bits.chars().any(|e| e != '0' && e != '1') => {
+                Err("truth table bits must be 0 or 1".to_string())
+            }
+            TruthTable::Bits(bits) if bits.len() != entries as usize => {
+                Err(format!("truth table has {} bits instead of {}", bits.len(), entries))
+            }
+            TruthTable::Minterms(minterms) => match minterms.iter().find(|e| **e >= entries) {
+                Some(minterm) => Err(format!("minterm {} is not below {}", minterm, entries)),
+                None => Ok(()),
+            },
+            _ => Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoxelLut
+{
+    /// Number of inputs (2-8)
+    #[serde(default = "default_gate_inputs")]
+    pub inputs: u8,
+    #[serde(default)]
+    pub table: TruthTable,
+    pub powered: bool,
+}
+
+impl Default for VoxelLut
+{
+    fn default() -> Self {
+        Self {
+            inputs: MIN_GATE_INPUTS,
+            table: Default::default(),
+            powered: false,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct VoxelMemory
 {
@@ -59,6 +386,132 @@ pub struct VoxelMemory
     pub powered: bool,
 }
 
+/// Clock edge on which a flip-flop updates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ClockEdge
+{
+    #[default]
+    #[serde(rename = "rising")]
+    Rising,
+    #[serde(rename = "falling")]
+    Falling,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelFlipFlop
+{
+    #[serde(default)]
+    pub edge: ClockEdge,
+    /// Whether the clock input was powered during the last tick
+    #[serde(default)]
+    pub clock: bool,
+    pub stored: bool,
+}
+
+/// Repeater outputting its input from `delay` ticks ago
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelRepeater
+{
+    /// Number of ticks between the input and the output (from 1 to `MAX_REPEATER_DELAY`)
+    pub delay: u32,
+    /// Inputs still in flight, oldest first
+    #[serde(default)]
+    pub history: VecDeque<PowerState>,
+    pub powered: bool,
+}
+
+/// How a comparator computes its output from its inputs `a` and `b`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ComparatorMode
+{
+    /// Output `a` if it is at least `b`
+    #[default]
+    #[serde(rename = "compare")]
+    Compare,
+    /// Output `a - b` (or nothing if `b` is greater)
+    #[serde(rename = "subtract")]
+    Subtract,
+}
+
+/// Comparator of two signal strengths (see `MAX_STRENGTH`)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelComparator
+{
+    #[serde(default)]
+    pub mode: ComparatorMode,
+    #[serde(default)]
+    pub value: Signal,
+}
+
+/// Amplifier multiplying the strength of its input by `gain` (up to `MAX_STRENGTH`)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelAmplifier
+{
+    pub gain: u8,
+    #[serde(default)]
+    pub value: Signal,
+}
+
+fn default_select_bits() -> u8
+{
+    MIN_SELECT_BITS
+}
+
+/// Multiplexer, demultiplexer, encoder or decoder between `2^select_bits` lines and `select_bits` lines
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoxelSelector
+{
+    /// Number of select (or binary) lines (1-3)
+    #[serde(default = "default_select_bits")]
+    pub select_bits: u8,
+    /// Signal on each output terminal, in terminal order
+    #[serde(default)]
+    pub outputs: Vec<Signal>,
+}
+
+impl Default for VoxelSelector
+{
+    fn default() -> Self {
+        Self {
+            select_bits: MIN_SELECT_BITS,
+            outputs: vec![],
+        }
+    }
+}
+
+/// Read-only memory, whose contents are loaded from `file` (relative to `ROM_DIRECTORY`) when placed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelRom
+{
+    /// Number of words (at most `MAX_MEMORY_DEPTH`)
+    pub depth: u32,
+    /// Width of each word in bits (at most `MAX_BUS_WIDTH`)
+    pub word_width: u8,
+    #[serde(default)]
+    pub file: String,
+    /// Stored words (words past the end are 0)
+    #[serde(default)]
+    pub contents: Vec<Signal>,
+    pub value: Signal,
+}
+
+/// Random-access memory, whose contents are saved with the world
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelRam
+{
+    /// Number of words (at most `MAX_MEMORY_DEPTH`)
+    pub depth: u32,
+    /// Width of each word in bits (at most `MAX_BUS_WIDTH`)
+    pub word_width: u8,
+    /// Stored words (words past the end are 0)
+    #[serde(default)]
+    pub contents: Vec<Signal>,
+    /// Whether the clock input was powered during the last tick
+    #[serde(default)]
+    pub clock: bool,
+    pub value: Signal,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct VoxelPulseLatch
 {
@@ -67,24 +520,215 @@ pub struct VoxelPulseLatch
     pub powered: bool,
 }
 
+/// Push-button which stays powered for `press_ticks` ticks after being pressed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelButton
+{
+    pub press_ticks: u32,
+    /// Set by the client to press the button (cleared by the server once the press is handled)
+    #[serde(default)]
+    pub pressed: bool,
+    /// Ticks left before the button is released
+    #[serde(default)]
+    pub remaining: u32,
+    pub powered: bool,
+}
+
+/// Keypad outputting the last key pressed (0-15), with `powered` set for `press_ticks` ticks after each press
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelKeypad
+{
+    pub press_ticks: u32,
+    #[serde(default)]
+    pub pressed: bool,
+    #[serde(default)]
+    pub remaining: u32,
+    pub value: Signal,
+    pub powered: bool,
+}
+
+/// Dial outputting a value between 0 and `max`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelDial
+{
+    pub max: Signal,
+    pub value: Signal,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct VoxelPixel
 {
     pub powered: bool,
 }
 
+/// Where a color pixel's color comes from
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode")]
+pub enum ColorSource
+{
+    /// Single input choosing between two colors
+    #[serde(rename = "switch")]
+    Switch {
+        on: Color,
+        off: Color,
+    },
+    /// 3-8 inputs giving the color's red, green and blue bits (most significant first, e.g. RRRGGGBB for 8 inputs)
+    #[serde(rename = "rgb")]
+    Rgb {
+        inputs: u8,
+    },
+    /// 3-8 inputs giving an index into a list of colors (black if out of range)
+    #[serde(rename = "palette")]
+    Palette {
+        inputs: u8,
+        palette: Vec<Color>,
+    },
+}
+
+impl Default for ColorSource
+{
+    fn default() -> Self {
+        ColorSource::Switch {
+            on: WHITE,
+            off: BLACK,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VoxelColorPixel
+{
+    #[serde(default)]
+    pub source: ColorSource,
+    /// Color currently shown
+    #[serde(default = "default_color")]
+    pub color: Color,
+}
+
+fn default_color() -> Color
+{
+    BLACK
+}
+
+impl Default for VoxelColorPixel
+{
+    fn default() -> Self {
+        Self {
+            source: Default::default(),
+            color: BLACK,
+        }
+    }
+}
+
+/// Seven-segment display, with segment `a` as bit 0 through segment `g` as bit 6 and the decimal point as bit 7
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelSevenSegment
+{
+    pub segments: u8,
+}
+
+/// Seven-segment display showing a hexadecimal digit (segments are numbered as in `VoxelSevenSegment`)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelHexDigit
+{
+    pub value: u8,
+    pub segments: u8,
+}
+
+/// Grid of colored pixels written one at a time, stored row-major from the top-left pixel
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelDisplay
+{
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub pixels: Vec<Color>,
+    /// Whether the strobe input was powered during the last tick
+    #[serde(default)]
+    pub strobe: bool,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct VoxelBlock
 {}
 
+/// Single-bit wire, which only joins adjacent wires of the same `color` (it connects to other blocks' terminals regardless)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelWire
+{
+    pub powered: bool,
+    #[serde(default)]
+    pub color: u8,
+    /// Signal strength in analog scenes (0 in digital scenes)
+    #[serde(default)]
+    pub strength: u8,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelBus
+{
+    pub width: u8,
+    pub value: Signal,
+}
+
+/// Wire crossing with independent channels along the block's X and Z axes
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelCrossing
+{
+    pub x: bool,
+    pub z: bool,
+}
+
+/// Source powered for `high_ticks` then unpowered for `low_ticks`, repeating from `start_tick`
+///
+/// `phase` shifts the cycle forward by that many ticks. A clock with `enable` set has an `enable` input
+/// which must be powered for the clock to output anything (the cycle keeps running while it is disabled)
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "ClockData")]
 pub struct VoxelClock
 {
-    pub rate: u32,
+    pub high_ticks: u32,
+    pub low_ticks: u32,
+    pub phase: u32,
     pub start_tick: u32,
+    pub enable: bool,
     pub powered: bool,
 }
 
+/// Serialized form of `VoxelClock`, which also accepts the single-tick `rate` of older scenes
+#[derive(Deserialize)]
+struct ClockData
+{
+    high_ticks: Option<u32>,
+    low_ticks: Option<u32>,
+    rate: Option<u32>,
+    #[serde(default)]
+    phase: u32,
+    #[serde(default)]
+    start_tick: u32,
+    #[serde(default)]
+    enable: bool,
+    #[serde(default)]
+    powered: bool,
+}
+
+impl From<ClockData> for VoxelClock
+{
+    fn from(data: ClockData) -> Self {
+        let high_ticks = data.high_ticks.unwrap_or(1);
+        let low_ticks = data.low_ticks.unwrap_or(data.rate.map_or(0, |e| e.saturating_sub(high_ticks)));
+
+        VoxelClock {
+            high_ticks,
+            low_ticks,
+            phase: data.phase,
+            start_tick: data.start_tick,
+            enable: data.enable,
+            powered: data.powered,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct VoxelPulse
 {
@@ -93,44 +737,197 @@ pub struct VoxelPulse
     pub powered: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Source of pseudo-random bits from a xorshift generator seeded by `seed`
+///
+/// The generator advances every tick, or on each rising edge of its `clk` input when `clocked` is set.
+/// Its `state` is saved with the block, so a saved world keeps producing the same sequence
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelRandom
+{
+    pub seed: u64,
+    #[serde(default)]
+    pub clocked: bool,
+    /// Generator state (0 until the block is placed, when it is set from the seed)
+    #[serde(default)]
+    pub state: u64,
+    /// Whether the clock input was powered during the last tick
+    #[serde(default)]
+    pub clock: bool,
+    #[serde(default)]
+    pub powered: bool,
+}
+
+/// Wireless endpoint on the named `channel`
+///
+/// Receivers output the bitwise OR of the signals at the inputs of all transmitters on their channel
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelChannel
+{
+    pub channel: String,
+    /// Signal at the transmitter's input or at the receiver's output
+    #[serde(default)]
+    pub signal: Signal,
+}
+
+/// What a monostable does when triggered again before its pulse has ended
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Retrigger
+{
+    /// Restart the pulse, so that it lasts `pulse_ticks` from the latest trigger
+    #[default]
+    #[serde(rename = "extend")]
+    Extend,
+    /// Let the current pulse finish unchanged
+    #[serde(rename = "ignore")]
+    Ignore,
+}
+
+/// Pulse generator powered for `pulse_ticks` ticks after each rising edge of its `trigger` input
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelMonostable
+{
+    pub pulse_ticks: u32,
+    #[serde(default)]
+    pub retrigger: Retrigger,
+    /// Whether the trigger input was powered during the last tick
+    #[serde(default)]
+    pub trigger: bool,
+    /// Ticks left in the current pulse
+    #[serde(default)]
+    pub remaining: u32,
+    #[serde(default)]
+    pub powered: bool,
+}
+
+/// Direction of a module port, as seen from outside of the module
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PortDirection
+{
+    #[default]
+    #[serde(rename = "input")]
+    Input,
+    #[serde(rename = "output")]
+    Output,
+}
+
+/// Named terminal of a module, placed inside the scene the module is made from
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelPort
+{
+    pub name: String,
+    pub direction: PortDirection,
+    pub value: Signal,
+}
+
+/// Sub-circuit simulated as a nested scene, whose ports become the module's terminals
+///
+/// The scene is loaded from `file` (relative to `MODULE_DIRECTORY`) when placed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelModule
+{
+    #[serde(default)]
+    pub file: String,
+    #[serde(default)]
+    pub scene: Box<Scene>,
+}
+
+/// Block defined outside of this enum (see `BlockBehavior`), identified by its kind name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoxelCustom
+{
+    pub kind: String,
+    #[serde(default)]
+    pub state: CustomState,
+    #[serde(default)]
+    pub outputs: CustomState,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "block", content = "data")]
 pub enum Block
 {
+    #[default]
     #[serde(rename = "air")]
     Air,
 
     #[serde(rename = "wire")]
-    Wire(VoxelPowered),
+    Wire(VoxelWire),
+    #[serde(rename = "bus")]
+    Bus(VoxelBus),
+    #[serde(rename = "bus_splitter")]
+    BusSplitter(VoxelBus),
+    #[serde(rename = "bus_merger")]
+    BusMerger(VoxelBus),
+    #[serde(rename = "crossing")]
+    Crossing(VoxelCrossing),
     #[serde(rename = "block")]
     Block(VoxelBlock),
     #[serde(rename = "toggle")]
     Toggle(VoxelPowered),
+    #[serde(rename = "button")]
+    Button(VoxelButton),
+    #[serde(rename = "keypad")]
+    Keypad(VoxelKeypad),
+    #[serde(rename = "dial")]
+    Dial(VoxelDial),
     #[serde(rename = "pixel")]
     Pixel(VoxelPixel),
+    #[serde(rename = "color_pixel")]
+    ColorPixel(VoxelColorPixel),
+    #[serde(rename = "display")]
+    Display(VoxelDisplay),
+    #[serde(rename = "seven_segment")]
+    SevenSegment(VoxelSevenSegment),
+    #[serde(rename = "hex_digit")]
+    HexDigit(VoxelHexDigit),
 
     #[serde(rename = "and_gate")]
-    ANDGate(VoxelPowered),
+    ANDGate(VoxelGate),
     #[serde(rename = "or_gate")]
-    ORGate(VoxelPowered),
+    ORGate(VoxelGate),
     #[serde(rename = "xor_gate")]
-    XORGate(VoxelPowered),
+    XORGate(VoxelGate),
     #[serde(rename = "nand_gate")]
-    NANDGate(VoxelPowered),
+    NANDGate(VoxelGate),
     #[serde(rename = "nor_gate")]
-    NORGate(VoxelPowered),
+    NORGate(VoxelGate),
     #[serde(rename = "xnor_gate")]
-    XNORGate(VoxelPowered),
+    XNORGate(VoxelGate),
     #[serde(rename = "not_gate")]
     NOTGate(VoxelPowered),
     #[serde(rename = "diode")]
     Diode(VoxelPowered),
+    #[serde(rename = "lut")]
+    Lut(VoxelLut),
+    #[serde(rename = "repeater")]
+    Repeater(VoxelRepeater),
+    #[serde(rename = "comparator")]
+    Comparator(VoxelComparator),
+    #[serde(rename = "amplifier")]
+    Amplifier(VoxelAmplifier),
+    #[serde(rename = "mux")]
+    Mux(VoxelSelector),
+    #[serde(rename = "demux")]
+    Demux(VoxelSelector),
+    #[serde(rename = "encoder")]
+    Encoder(VoxelSelector),
+    #[serde(rename = "decoder")]
+    Decoder(VoxelSelector),
 
     #[serde(rename = "clock")]
     Clock(VoxelClock),
 
     #[serde(rename = "pulse")]
     Pulse(VoxelPulse),
+    #[serde(rename = "monostable")]
+    Monostable(VoxelMonostable),
+    #[serde(rename = "random")]
+    Random(VoxelRandom),
+    #[serde(rename = "transmitter")]
+    Transmitter(VoxelChannel),
+    #[serde(rename = "receiver")]
+    Receiver(VoxelChannel),
 
     #[serde(rename = "toggle_latch")]
     ToggleLatch(VoxelMemory),
@@ -138,6 +935,26 @@ pub enum Block
     PulseLatch(VoxelPulseLatch),
     #[serde(rename = "memory_latch")]
     MemoryLatch(VoxelMemory),
+    #[serde(rename = "d_flip_flop")]
+    DFlipFlop(VoxelFlipFlop),
+    #[serde(rename = "t_flip_flop")]
+    TFlipFlop(VoxelFlipFlop),
+    #[serde(rename = "jk_flip_flop")]
+    JKFlipFlop(VoxelFlipFlop),
+    #[serde(rename = "sr_flip_flop")]
+    SRFlipFlop(VoxelFlipFlop),
+    #[serde(rename = "rom")]
+    Rom(VoxelRom),
+    #[serde(rename = "ram")]
+    Ram(VoxelRam),
+
+    #[serde(rename = "port")]
+    Port(VoxelPort),
+    #[serde(rename = "module")]
+    Module(VoxelModule),
+
+    #[serde(rename = "custom")]
+    Custom(VoxelCustom),
 }
 
 impl Display for Block
@@ -147,105 +964,110 @@ impl Display for Block
     }
 }
 
-impl Default for Block
-{
-    fn default() -> Self {
-        Self::Air
-    }
-}
-
 impl Block
 {
-    /// Get this block's power state and independence by voxel (returns value only if applicable)
-    ///
-    /// Internal: MUST match voxel definitions in `get_structure`
-    pub fn get_circuit_voxel_power(&self) -> HashMap<VoxelID, Option<PowerState>> {
+    /// Get the name of this block's kind (as used by the protocol and the block registry)
+    pub fn kind_name(&self) -> &str {
         match self {
-            Block::Air => Default::default(),
-            Block::Wire(data) => [(circuit_voxel("wire"), Some(data.powered))].into(),
-            Block::Block(_) => Default::default(),
-            Block::Toggle(data) => [(circuit_voxel("toggle"), Some(data.powered))].into(),
-            Block::Pixel(_) => [(circuit_voxel("pixel"), None)].into(),
-            Block::ANDGate(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::ORGate(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::XORGate(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::NANDGate(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::NORGate(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::XNORGate(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::NOTGate(data) => [
-                (circuit_voxel("in"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::Clock(data) => [
-                (circuit_voxel("clock"), Some(data.powered)),
-            ].into(),
-            Block::Pulse(data) => [
-                (circuit_voxel("pulse"), Some(data.powered)),
-            ].into(),
-            Block::Diode(data) => [
-                (circuit_voxel("in"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::ToggleLatch(data) => [
-                (circuit_voxel("in"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::PulseLatch(data) => [
-                (circuit_voxel("in"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
-            Block::MemoryLatch(data) => [
-                (circuit_voxel("in_a"), None),
-                (circuit_voxel("in_b"), None),
-                (circuit_voxel("out"), Some(data.powered)),
-            ].into(),
+            Block::Air => "air",
+            Block::Wire(_) => "wire",
+            Block::Bus(_) => "bus",
+            Block::BusSplitter(_) => "bus_splitter",
+            Block::BusMerger(_) => "bus_merger",
+            Block::Crossing(_) => "crossing",
+            Block::Block(_) => "block",
+            Block::Toggle(_) => "toggle",
+            Block::Button(_) => "button",
+            Block::Keypad(_) => "keypad",
+            Block::Dial(_) => "dial",
+            Block::Pixel(_) => "pixel",
+            Block::ColorPixel(_) => "color_pixel",
+            Block::Display(_) => "display",
+            Block::SevenSegment(_) => "seven_segment",
+            Block::HexDigit(_) => "hex_digit",
+            Block::ANDGate(_) => "and_gate",
+            Block::ORGate(_) => "or_gate",
+            Block::XORGate(_) => "xor_gate",
+            Block::NANDGate(_) => "nand_gate",
+            Block::NORGate(_) => "nor_gate",
+            Block::XNORGate(_) => "xnor_gate",
+            Block::NOTGate(_) => "not_gate",
+            Block::Diode(_) => "diode",
+            Block::Lut(_) => "lut",
+            Block::Repeater(_) => "repeater",
+            Block::Comparator(_) => "comparator",
+            Block::Amplifier(_) => "amplifier",
+            Block::Mux(_) => "mux",
+            Block::Demux(_) => "demux",
+            Block::Encoder(_) => "encoder",
+            Block::Decoder(_) => "decoder",
+            Block::Clock(_) => "clock",
+            Block::Pulse(_) => "pulse",
+            Block::Monostable(_) => "monostable",
+            Block::Random(_) => "random",
+            Block::Transmitter(_) => "transmitter",
+            Block::Receiver(_) => "receiver",
+            Block::ToggleLatch(_) => "toggle_latch",
+            Block::PulseLatch(_) => "pulse_latch",
+            Block::MemoryLatch(_) => "memory_latch",
+            Block::DFlipFlop(_) => "d_flip_flop",
+            Block::TFlipFlop(_) => "t_flip_flop",
+            Block::JKFlipFlop(_) => "jk_flip_flop",
+            Block::SRFlipFlop(_) => "sr_flip_flop",
+            Block::Rom(_) => "rom",
+            Block::Ram(_) => "ram",
+            Block::Port(_) => "port",
+            Block::Module(_) => "module",
+            Block::Custom(data) => &data.kind,
         }
     }
 
-    /// Get this block's power state (returns value only if applicable)
-    pub fn get_circuit_power(&self) -> Option<PowerState> {
-        match self {
-            Block::Wire(data) => Some(data.powered),
-            Block::Toggle(data) => Some(data.powered),
-            Block::ANDGate(data) => Some(data.powered),
-            Block::ORGate(data) => Some(data.powered),
-            Block::XORGate(data) => Some(data.powered),
-            Block::NANDGate(data) => Some(data.powered),
-            Block::NORGate(data) => Some(data.powered),
-            Block::XNORGate(data) => Some(data.powered),
-            Block::NOTGate(data) => Some(data.powered),
-            Block::Clock(data) => Some(data.powered),
-            Block::Pulse(data) => Some(data.powered),
-            Block::Diode(data) => Some(data.powered),
-            Block::ToggleLatch(data) => Some(data.powered),
-            Block::PulseLatch(data) => Some(data.powered),
-            Block::MemoryLatch(data) => Some(data.powered),
-            _ => None
-        }
+    /// Get this block's kind from the block registry
+    pub fn kind(&self) -> Arc<dyn BlockKind> {
+        get_kind(self.kind_name())
+    }
+
+    /// Prepare the block to be placed (see `BlockKind::prepare`)
+    pub fn prepare(&mut self) -> Result<(), String> {
+        self.kind().prepare(self)
+    }
+
+    /// Get this block's signal and independence by voxel (returns value only if applicable)
+    pub fn get_circuit_voxel_signal(&self) -> HashMap<VoxelID, Option<Signal>> {
+        let kind = self.kind();
+        let outputs = kind.outputs(self);
+
+        kind.structure(self)
+            .into_keys()
+            .filter(|e| is_circuit_voxel(e))
+            .map(|e| {
+                let signal = outputs.get(&e).copied();
+                (e, signal)
+            })
+            .collect()
+    }
+
+    /// Get this block's power state (i.e. if any of its outputs are powered)
+    pub fn get_circuit_power(&self) -> PowerState {
+        self.kind()
+            .outputs(self)
+            .values()
+            .any(|e| *e != 0)
+    }
+
+    /// Get the width of the network this block conducts (returns value only for wires and buses)
+    pub fn get_conductor_width(&self) -> Option<u8> {
+        self.kind().conductor_width(self)
+    }
+
+    /// Get the insulation color of the block, if any (see `BlockKind::conductor_color`)
+    pub fn get_conductor_color(&self) -> Option<u8> {
+        self.kind().conductor_color(self)
+    }
+
+    /// Return true if the block is part of a contiguous network (i.e. a wire or a bus)
+    pub fn is_conductor(&self) -> bool {
+        self.get_conductor_width().is_some()
     }
 
     /// Get all of the circuit voxels (global) belonging to this block
@@ -257,51 +1079,27 @@ impl Block
             .collect()
     }
 
+    /// Get the directions (global) in which the block's terminals connect (see `BlockKind::terminal_faces`)
+    pub fn get_global_terminal_faces(&self, orientation: Orient) -> HashMap<VoxelID, Vec<Coord>> {
+        self.kind()
+            .terminal_faces(self)
+            .into_iter()
+            .map(|(id, faces)| (id, faces.into_iter().map(|e| orientation.rotate(e)).collect()))
+            .collect()
+    }
+
     /// Return true if the block is a circuit block (i.e. has at least one circuit voxel)
     pub fn is_circuit_block(&self) -> bool {
         self.get_structure()
-            .iter()
-            .position(|(id, _)| is_circuit_voxel(id))
-            .is_some()
+            .keys()
+            .any(|id| is_circuit_voxel(id))
     }
 
     /// Return the voxels that make up the block, adjusted to contain global coordinates
     pub fn get_global_structure(&self, position: Coord, orientation: Orient) -> HashMap<VoxelID, Coord> {
         self.get_structure()
             .into_iter()
-            .map(|(id, mut coord)| {
-                match orientation {
-                    Orient::FORWARD => {
-                        // Default orientation, do nothing
-                    }
-                    Orient::RIGHT => {
-                        let t = coord.x;
-                        coord.x = coord.z;
-                        coord.z = t;
-                    }
-                    Orient::LEFT => {
-                        let t = coord.x;
-                        coord.x = -coord.z;
-                        coord.z = -t;
-                    }
-                    Orient::BACKWARD => {
-                        coord.z = -coord.z;
-                        coord.x = -coord.x;
-                    }
-                    Orient::UPWARD => {
-                        let t = coord.y;
-                        coord.y = coord.z;
-                        coord.z = t;
-                    }
-                    Orient::DOWNWARD => {
-                        let t = coord.y;
-                        coord.y = -coord.z;
-                        coord.z = -t;
-                    }
-                }
-
-                (id, coord + position)
-            })
+            .map(|(id, coord)| (id, orientation.rotate(coord) + position))
             .collect()
     }
 
@@ -309,111 +1107,51 @@ impl Block
     ///
     /// Voxels whose names start with an exclamation are considered as part of the circuit
     pub fn get_structure(&self) -> HashMap<VoxelID, Coord> {
-        match self {
-            Block::Air => Default::default(),
-            Block::Wire(_) => [(circuit_voxel("wire"), Coord::zero())].into(),
-            Block::Block(_) => [("block".to_string(), Coord::zero())].into(),
-            Block::Toggle(_) => [(circuit_voxel("toggle"), Coord::zero())].into(),
-            Block::Pixel(_) => [(circuit_voxel("pixel"), Coord::zero())].into(),
-            Block::ANDGate(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::ORGate(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::XORGate(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::NANDGate(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::NORGate(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::XNORGate(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::NOTGate(_) => [
-                (circuit_voxel("in"), Coord::new(0, 0, 0)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::Clock(_) => [
-                (circuit_voxel("clock"), Coord::zero()),
-            ].into(),
-            Block::Pulse(_) => [
-                (circuit_voxel("pulse"), Coord::zero()),
-            ].into(),
-            Block::Diode(_) => [
-                (circuit_voxel("in"), Coord::new(0, 0, 0)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::ToggleLatch(_) => [
-                (circuit_voxel("in"), Coord::new(0, 0, 0)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::PulseLatch(_) => [
-                (circuit_voxel("in"), Coord::new(0, 0, 0)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
-            Block::MemoryLatch(_) => [
-                (format!("solid-{}", 0), Coord::new(0, 0, 0)),
-                (format!("solid-{}", 1), Coord::new(-1, 0, 1)),
-                (format!("solid-{}", 2), Coord::new(1, 0, 1)),
-                (format!("solid-{}", 3), Coord::new(-1, 0, 0)),
-                (format!("solid-{}", 4), Coord::new(1, 0, 0)),
-                (format!("solid-{}", 5), Coord::new(0, 0, -1)),
-                (circuit_voxel("in_a"), Coord::new(-1, 0, -1)),
-                (circuit_voxel("in_b"), Coord::new(1, 0, -1)),
-                (circuit_voxel("out"), Coord::new(0, 0, 1)),
-            ].into(),
+        self.kind().structure(self)
+    }
+}
+
+#[test]
+fn orient_test()
+{
+    use std::collections::HashSet;
+
+    fn determinant(m: Rotation) -> i32 {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) -
+            m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0]) +
+            m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // All 24 orientations are distinct proper rotations
+    let matrices = Orient::all().map(|e| e.matrix()).collect::<HashSet<_>>();
+    assert_eq!(matrices.len(), 24);
+    assert!(matrices.iter().all(|e| determinant(*e) == 1));
+
+    // Mirrored orientations are the 24 improper rotations, and orientations compose
+    let mirrored = Orient::all().map(|e| e.with_mirror(Some(Axis::Y)).matrix()).collect::<HashSet<_>>();
+    assert_eq!(mirrored.len(), 24);
+    assert!(mirrored.iter().all(|e| determinant(*e) == -1));
+    for inner in Orient::all().map(|e| e.with_mirror(Some(Axis::Z))) {
+        for outer in Orient::all().chain(Orient::all().map(|e| e.with_mirror(Some(Axis::X)))) {
+            let coord = Coord::new(1, 2, 3);
+            assert_eq!(inner.within(outer).rotate(coord), outer.rotate(inner.rotate(coord)));
         }
     }
-}
\ No newline at end of file
+
+    // Facings point the block's front where they say
+    for (facing, front) in Facing::ALL.into_iter().zip([(0, 0, 1), (1, 0, 0), (-1, 0, 0), (0, 0, -1), (0, 1, 0), (0, -1, 0)]) {
+        for roll in 0..4 {
+            assert_eq!(Orient::new(facing, roll).rotate(Coord::unit_z()), front.into());
+        }
+    }
+
+    // Orientations without roll keep the original encoding
+    assert_eq!(serde_json::to_string(&Orient::RIGHT).unwrap(), r#""RIGHT""#);
+    assert_eq!(serde_json::from_str::<Orient>(r#""UPWARD""#).unwrap(), Orient::UPWARD);
+    let rolled = Orient::new(Facing::LEFT, 3);
+    assert_eq!(serde_json::to_string(&rolled).unwrap(), r#"{"facing":"LEFT","roll":3}"#);
+    assert_eq!(serde_json::from_str::<Orient>(r#"{"facing":"LEFT","roll":3}"#).unwrap(), rolled);
+    let mirrored = Orient::FORWARD.with_mirror(Some(Axis::X));
+    assert_eq!(serde_json::to_string(&mirrored).unwrap(), r#"{"facing":"FORWARD","roll":0,"mirror":"x"}"#);
+    assert_eq!(serde_json::from_str::<Orient>(r#"{"facing":"FORWARD","mirror":"x"}"#).unwrap(), mirrored);
+}
@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::behavior::{BlockBehavior, CustomLayout};
+use crate::block::{CustomState, Signal};
+use crate::grid::Coord;
+use crate::registry::{Context, registry};
+
+/// Default location of the block definitions file loaded at startup
+pub const DEFINITIONS_PATH: &str = "./blocks.json";
+
+/// Boolean expression used by block definitions
+///
+/// Supports `!` (not), `&` (and), `^` (xor), `|` (or), parentheses, the constants `0` and `1`, and names
+/// (of input terminals and state fields, where any non-zero signal is considered powered)
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression
+{
+    Constant(bool),
+    Name(String),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Xor(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+}
+
+impl Expression
+{
+    /// Parse an expression from a string
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut chars = source.chars().peekable();
+        let result = Self::parse_or(&mut chars)?;
+
+        match Self::skip_whitespace(&mut chars) {
+            None => Ok(result),
+            Some(c) => Err(format!("unexpected '{}' in expression \"{}\"", c, source)),
+        }
+    }
+
+    /// Evaluate the expression, looking up names with the given function
+    pub fn evaluate(&self, lookup: &impl Fn(&str) -> Signal) -> bool {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Name(name) => lookup(name) != 0,
+            Expression::Not(e) => !e.evaluate(lookup),
+            Expression::And(a, b) => a.evaluate(lookup) && b.evaluate(lookup),
+            Expression::Xor(a, b) => a.evaluate(lookup) != b.evaluate(lookup),
+            Expression::Or(a, b) => a.evaluate(lookup) || b.evaluate(lookup),
+        }
+    }
+
+    /// Get all names referenced by the expression
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Expression::Constant(_) => vec![],
+            Expression::Name(name) => vec![name],
+            Expression::Not(e) => e.names(),
+            Expression::And(a, b) | Expression::Xor(a, b) | Expression::Or(a, b) =>
+                a.names().into_iter().chain(b.names()).collect(),
+        }
+    }
+
+    /// Skip whitespace and peek at the next character
+    fn skip_whitespace(chars: &mut Peekable<Chars>) -> Option<char> {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        chars.peek().copied()
+    }
+
+    fn parse_or(chars: &mut Peekable<Chars>) -> Result<Self, String> {
+        let mut result = Self::parse_xor(chars)?;
+        while Self::skip_whitespace(chars) == Some('|') {
+            chars.next();
+            result = Expression::Or(Box::new(result), Box::new(Self::parse_xor(chars)?));
+        }
+        Ok(result)
+    }
+
+    fn parse_xor(chars: &mut Peekable<Chars>) -> Result<Self, String> {
+        let mut result = Self::parse_and(chars)?;
+        while Self::skip_whitespace(chars) == Some('^') {
+            chars.next();
+            result = Expression::Xor(Box::new(result), Box::new(Self::parse_and(chars)?));
+        }
+        Ok(result)
+    }
+
+    fn parse_and(chars: &mut Peekable<Chars>) -> Result<Self, String> {
+        let mut result = Self::parse_unary(chars)?;
+        while Self::skip_whitespace(chars) == Some('&') {
+            chars.next();
+            result = Expression::And(Box::new(result), Box::new(Self::parse_unary(chars)?));
+        }
+        Ok(result)
+    }
+
+    fn parse_unary(chars: &mut Peekable<Chars>) -> Result<Self, String> {
+        match Self::skip_whitespace(chars) {
+            Some('!') => {
+                chars.next();
+                Ok(Expression::Not(Box::new(Self::parse_unary(chars)?)))
+            }
+            Some('(') => {
+                chars.next();
+                let result = Self::parse_or(chars)?;
+                match Self::skip_whitespace(chars) {
+                    Some(')') => {
+                        chars.next();
+                        Ok(result)
+                    }
+                    _ => Err("expected ')' in expression".to_string()),
+                }
+            }
+            Some('0') => {
+                chars.next();
+                Ok(Expression::Constant(false))
+            }
+            Some('1') => {
+                chars.next();
+                Ok(Expression::Constant(true))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                Ok(Expression::Name(name))
+            }
+            Some(c) => Err(format!("unexpected '{}' in expression", c)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// Output terminal of a block definition
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputDefinition
+{
+    pub position: Coord,
+    /// Expression (over state fields) giving the terminal's signal
+    pub value: String,
+}
+
+/// Block kind described by data, as found in the block definitions file
+///
+/// Blocks of defined kinds are placed as `Block::Custom`, whose state holds the definition's state fields
+/// and whose outputs are computed from the state after every tick
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockDefinition
+{
+    pub name: String,
+    /// Non-circuit voxels
+    #[serde(default)]
+    pub solids: Vec<Coord>,
+    /// Input terminal positions by name
+    #[serde(default)]
+    pub inputs: BTreeMap<String, Coord>,
+    /// Output terminals by name
+    #[serde(default)]
+    pub outputs: BTreeMap<String, OutputDefinition>,
+    /// State fields and their initial values
+    #[serde(default)]
+    pub state: BTreeMap<String, Signal>,
+    /// State updates performed in order every tick, as `(state field, expression over inputs and state fields)`
+    #[serde(default)]
+    pub update: Vec<(String, String)>,
+}
+
+/// Block behavior implemented by a `BlockDefinition`
+pub struct DefinedKind
+{
+    definition: BlockDefinition,
+    outputs: Vec<(String, Expression)>,
+    update: Vec<(String, Expression)>,
+}
+
+impl DefinedKind
+{
+    /// Check and prepare the given definition
+    pub fn new(definition: BlockDefinition) -> Result<Self, String> {
+        let name = &definition.name;
+
+        // Check that terminal and state field names are unique
+        for input in definition.inputs.keys() {
+            if definition.outputs.contains_key(input) || definition.state.contains_key(input) {
+                return Err(format!("block \"{}\" uses the name \"{}\" more than once", name, input));
+            }
+        }
+        for output in definition.outputs.keys() {
+            if definition.state.contains_key(output) {
+                return Err(format!("block \"{}\" uses the name \"{}\" more than once", name, output));
+            }
+        }
+
+        // Check that names used by the expressions exist
+        let check = |expression: &str, allow_inputs: bool| {
+            let e = Expression::parse(expression)
+                .map_err(|e| format!("block \"{}\": {}", name, e))?;
+            match e.names()
+                .into_iter()
+                .find(|n| !(definition.state.contains_key(*n) || allow_inputs && definition.inputs.contains_key(*n))) {
+                Some(n) => Err(format!("block \"{}\": unknown name \"{}\" in expression \"{}\"", name, n, expression)),
+                None => Ok(e),
+            }
+        };
+
+        let outputs = definition.outputs
+            .iter()
+            .map(|(terminal, output)| Ok((terminal.clone(), check(&output.value, false)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let update = definition.update
+            .iter()
+            .map(|(field, expression)| if definition.state.contains_key(field) {
+                Ok((field.clone(), check(expression, true)?))
+            } else {
+                Err(format!("block \"{}\" updates unknown state field \"{}\"", name, field))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            definition,
+            outputs,
+            update,
+        })
+    }
+}
+
+impl BlockBehavior for DefinedKind
+{
+    fn layout(&self) -> CustomLayout {
+        CustomLayout {
+            solids: self.definition.solids.clone(),
+            inputs: self.definition.inputs.clone(),
+            outputs: self.definition.outputs
+                .iter()
+                .map(|(name, e)| (name.clone(), e.position))
+                .collect(),
+        }
+    }
+
+    fn evaluate(&self, state: &CustomState, inputs: &CustomState, _context: &Context) -> (CustomState, CustomState) {
+        // Fill in missing state fields with their initial values (ignoring unknown fields)
+        let mut state = self.definition.state
+            .iter()
+            .map(|(field, value)| (field.clone(), state.get(field).copied().unwrap_or(*value)))
+            .collect::<CustomState>();
+
+        for (field, e) in &self.update {
+            let value = e.evaluate(&|name| state
+                .get(name)
+                .or_else(|| inputs.get(name))
+                .copied()
+                .unwrap_or(0));
+            state.insert(field.clone(), Signal::from(value));
+        }
+
+        let outputs = self.outputs
+            .iter()
+            .map(|(name, e)| (name.clone(), Signal::from(e.evaluate(&|name| state[name]))))
+            .collect();
+
+        (state, outputs)
+    }
+}
+
+/// Load block definitions from the given file (a JSON list of `BlockDefinition`) into the registry
+///
+/// Returns the names of the registered block kinds
+pub fn load_definitions(path: &Path) -> Result<Vec<String>, String> {
+    let definitions: Vec<BlockDefinition> = serde_json::from_str(&std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read block definitions: {}", e))?)
+        .map_err(|e| format!("failed to parse block definitions: {}", e))?;
+
+    register_definitions(definitions)
+}
+
+/// Register the given block definitions
+///
+/// Returns the names of the registered block kinds
+pub fn register_definitions(definitions: Vec<BlockDefinition>) -> Result<Vec<String>, String> {
+    let kinds = definitions
+        .into_iter()
+        .map(|e| Ok((e.name.clone(), DefinedKind::new(e)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut registry = registry().write().unwrap();
+
+    // Check all names before registering anything
+    if let Some((name, _)) = kinds.iter().find(|(name, _)| registry.get(name).is_some()) {
+        return Err(format!("block kind \"{}\" is already defined", name));
+    }
+
+    Ok(kinds
+        .into_iter()
+        .map(|(name, kind)| {
+            registry.register_behavior(&name, Arc::new(kind));
+            name
+        })
+        .collect())
+}
+
+#[test]
+fn expression_test()
+{
+    let e = Expression::parse("!(a & b) | c ^ 1").unwrap();
+    let lookup = |a: Signal, b: Signal, c: Signal| move |name: &str| match name {
+        "a" => a,
+        "b" => b,
+        _ => c,
+    };
+
+    assert!(e.evaluate(&lookup(1, 0, 1)));
+    assert!(!e.evaluate(&lookup(1, 1, 1)));
+    assert!(e.evaluate(&lookup(1, 1, 0)));
+    assert_eq!(e.names(), vec!["a", "b", "c"]);
+
+    assert!(Expression::parse("a &").is_err());
+    assert!(Expression::parse("(a | b").is_err());
+    assert!(Expression::parse("a b").is_err());
+}
+
+#[test]
+fn definition_test()
+{
+    use crate::block::{Block, VoxelCustom, VoxelPowered, VoxelWire};
+    use crate::scene::Scene;
+
+    let definitions: Vec<BlockDefinition> = serde_json::from_str(r#"[{
+        "name": "test_majority_gate",
+        "solids": [{"x": 0, "y": 0, "z": 0}],
+        "inputs": {
+            "a": {"x": -1, "y": 0, "z": 0},
+            "b": {"x": 0, "y": 0, "z": -1},
+            "c": {"x": 1, "y": 0, "z": 0}
+        },
+        "outputs": {"out": {"position": {"x": 0, "y": 0, "z": 1}, "value": "powered"}},
+        "state": {"powered": 0},
+        "update": [["powered", "a & b | a & c | b & c"]]
+    }]"#).unwrap();
+    assert_eq!(register_definitions(definitions.clone()).unwrap(), vec!["test_majority_gate"]);
+    assert!(register_definitions(definitions).is_err());
+
+    let mut scene = Scene::default();
+    let gate = scene.add_block(Block::Custom(VoxelCustom {
+        kind: "test_majority_gate".to_string(),
+        ..Default::default()
+    }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(-2, 0, 0), Default::default()).unwrap();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -2), Default::default()).unwrap();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: false }), Coord::new(2, 0, 0), Default::default()).unwrap();
+    let wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(0, 0, 2), Default::default()).unwrap();
+
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(gate).unwrap().2, Block::Custom(VoxelCustom {
+        kind: "test_majority_gate".to_string(),
+        state: [("powered".to_string(), 1)].into(),
+        outputs: [("out".to_string(), 1)].into(),
+    }));
+    assert_eq!(scene.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: true, ..Default::default() }));
+
+    // Unknown names are rejected
+    let bad: Vec<BlockDefinition> = serde_json::from_str(r#"[{
+        "name": "test_bad_gate",
+        "inputs": {"a": {"x": 0, "y": 0, "z": 0}},
+        "state": {"powered": 0},
+        "update": [["powered", "a & b"]]
+    }]"#).unwrap();
+    assert!(register_definitions(bad).is_err());
+}
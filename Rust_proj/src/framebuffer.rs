@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::Color;
+use crate::grid::Coord;
+
+/// Largest width or height of a framebuffer (or display block)
+pub const MAX_FRAMEBUFFER_SIZE: u32 = 256;
+
+/// Named rectangle of pixels in the world which can be captured as an image
+///
+/// Columns run along +X and rows along -Y from `origin` (the top-left pixel), so it is viewed from -Z.
+/// Any pixel, color pixel or display block voxel within the rectangle is captured, other voxels are black
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Framebuffer
+{
+    pub origin: Coord,
+    pub width: u32,
+    pub height: u32,
+    /// Capture the framebuffer every this many ticks (only on demand if 0)
+    #[serde(default)]
+    pub capture_interval: u32,
+}
+
+impl Framebuffer
+{
+    /// Get the world location of the given pixel
+    pub fn location(&self, column: u32, row: u32) -> Coord {
+        self.origin + Coord::new(column as i32, -(row as i32), 0)
+    }
+}
+
+/// Captured image (row-major, starting from the top-left pixel)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame
+{
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>,
+}
+
+impl Frame
+{
+    /// Encode the frame as a binary PPM image (ignoring transparency)
+    pub fn to_ppm(&self) -> Vec<u8> {
+        format!("P6\n{} {}\n255\n", self.width, self.height)
+            .into_bytes()
+            .into_iter()
+            .chain(self.pixels.iter().flat_map(|(r, g, b, _)| [*r, *g, *b]))
+            .collect()
+    }
+}
+
+/// Check that the given framebuffer name can be used in a file name
+pub fn validate_framebuffer_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|e| e.is_ascii_alphanumeric() || e == '_' || e == '-') {
+        return Err(format!("framebuffer name \"{}\" must only contain letters, digits, '_' and '-'", name));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn framebuffer_test()
+{
+    use crate::block::{BLACK, Block, circuit_voxel, VoxelColorPixel, VoxelDisplay, VoxelPixel, WHITE};
+    use crate::registry::Context;
+    use crate::scene::{Scene, SceneData};
+
+    // Displays are written on the rising edge of their strobe
+    let mut display = Block::Display(VoxelDisplay { width: 2, height: 2, ..Default::default() });
+    display.prepare().unwrap();
+    for strobe in [1, 1, 0] {
+        let inputs = [("x", 1), ("y", 0), ("data", 0x102030), ("strobe", strobe)]
+            .map(|(name, e)| (circuit_voxel(name), e))
+            .into();
+        display.kind().evaluate(&mut display, &inputs, &Context::default());
+    }
+    assert_eq!(display, Block::Display(VoxelDisplay { width: 2, height: 2, pixels: vec![BLACK, (16, 32, 48, 255), BLACK, BLACK], strobe: false }));
+
+    // Framebuffers capture displays, pixels and color pixels alike
+    let mut scene = Scene::default();
+    scene.add_block(display, Coord::new(0, 0, 0), Default::default()).unwrap();
+    scene.add_block(Block::Pixel(VoxelPixel { powered: true }), Coord::new(2, 1, 0), Default::default()).unwrap();
+    scene.add_block(Block::ColorPixel(VoxelColorPixel { color: (1, 2, 3, 4), ..Default::default() }), Coord::new(2, 0, 0), Default::default()).unwrap();
+
+    assert!(scene.define_framebuffer("../screen", Framebuffer { origin: Coord::new(0, 1, 0), width: 3, height: 2, capture_interval: 0 }).is_err());
+    scene.define_framebuffer("screen", Framebuffer { origin: Coord::new(0, 1, 0), width: 3, height: 3, capture_interval: 10 }).unwrap();
+
+    let frame = Frame {
+        width: 3,
+        height: 3,
+        pixels: vec![BLACK, (16, 32, 48, 255), WHITE, BLACK, BLACK, (1, 2, 3, 4), BLACK, BLACK, BLACK],
+    };
+    assert_eq!(scene.capture_framebuffer("screen"), Some(frame.clone()));
+    assert_eq!(scene.capture_framebuffer("missing"), None);
+    assert_eq!(&frame.to_ppm()[..11], b"P6\n3 3\n255\n");
+    assert_eq!(frame.to_ppm().len(), 11 + 3 * 3 * 3);
+
+    // Framebuffers are saved with the world
+    let data = serde_json::to_string(&SceneData::from(scene.clone())).unwrap();
+    let loaded = Scene::from(serde_json::from_str::<SceneData>(&data).unwrap());
+    assert_eq!(loaded.get_framebuffers(), scene.get_framebuffers());
+    assert_eq!(loaded.capture_framebuffer("screen"), Some(frame));
+}
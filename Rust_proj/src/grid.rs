@@ -101,6 +101,7 @@ impl<T: Value> Grid<T>
     }
 
     /// Get all adjacent voxels (if they exist)
+    #[allow(clippy::useless_vec)]
     pub fn get_adjacent(&self, location: Coord) -> Vec<(Coord, &T)> {
         let offsets = vec![
             Vector3::new(-1, 0, 0),
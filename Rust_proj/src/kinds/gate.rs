@@ -0,0 +1,643 @@
+use std::collections::HashMap;
+
+use crate::block::{Block, circuit_voxel, ComparatorMode, indexed_voxel, is_circuit_voxel, MAX_GATE_INPUTS, MAX_REPEATER_DELAY, MAX_SELECT_BITS, MAX_STRENGTH, MIN_GATE_INPUTS, MIN_SELECT_BITS, PowerState, Signal, VoxelGate, VoxelID, VoxelPowered, VoxelSelector};
+use crate::grid::Coord;
+use crate::registry::{BlockKind, Context, input_power, input_signal};
+
+/// Return the voxels of a gate with the given input terminals along its back and output `out` at the front
+///
+/// Inputs are spaced two voxels apart (so that wires leaving adjacent inputs do not touch),
+/// making a gate with `n` inputs `2n - 1` voxels wide and 3 voxels deep
+pub fn gate_structure(inputs: &[VoxelID]) -> HashMap<VoxelID, Coord> {
+    gate_structure_with(inputs, &[circuit_voxel("out")])
+}
+
+/// Return the voxels of a gate with the given input terminals along its back and output terminals along its front
+///
+/// Terminals are laid out like in `gate_structure`, with each row centered on the block's origin
+pub fn gate_structure_with(inputs: &[VoxelID], outputs: &[VoxelID]) -> HashMap<VoxelID, Coord> {
+    let half_width = inputs.len().max(outputs.len()) as i32 - 1;
+
+    /// Place the given terminals two voxels apart along a row centered on the origin
+    fn row(terminals: &[VoxelID], z: i32) -> impl Iterator<Item=(VoxelID, Coord)> + '_ {
+        let offset = terminals.len() as i32 - 1;
+
+        terminals
+            .iter()
+            .enumerate()
+            .map(move |(i, e)| (e.clone(), Coord::new(2 * i as i32 - offset, 0, z)))
+    }
+
+    let terminals = row(inputs, -1)
+        .chain(row(outputs, 1))
+        .collect::<HashMap<VoxelID, Coord>>();
+
+    let solids = (-half_width..=half_width)
+        .flat_map(|x| (-1..=1).map(move |z| Coord::new(x, 0, z)))
+        .filter(|e| !terminals.values().any(|t| t == e))
+        .enumerate()
+        .map(|(i, e)| (format!("solid-{}", i), e))
+        .collect::<Vec<_>>();
+
+    terminals.into_iter().chain(solids).collect()
+}
+
+/// Get the faces of a gate-like block's terminals (see `BlockKind::terminal_faces`),
+/// where inputs only connect through their back (-Z) face and outputs only through their front (+Z) face
+pub fn gate_faces(kind: &dyn BlockKind, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+    let outputs = kind.outputs(block);
+
+    kind.structure(block)
+        .into_keys()
+        .filter(|e| is_circuit_voxel(e))
+        .map(|e| {
+            let face = if outputs.contains_key(&e) { Coord::new(0, 0, 1) } else { Coord::new(0, 0, -1) };
+            (e, vec![face])
+        })
+        .collect()
+}
+
+/// Get the input terminals of a gate with the given number of inputs (`in_0`, `in_1`, ...)
+pub fn gate_inputs(inputs: u8) -> Vec<VoxelID> {
+    (0..inputs.clamp(MIN_GATE_INPUTS, MAX_GATE_INPUTS))
+        .map(|i| indexed_voxel("in", i))
+        .collect()
+}
+
+/// Return the voxels of a 1x2 gate, with input `in` at the back and output `out` at the front
+pub fn unary_gate_structure() -> HashMap<VoxelID, Coord> {
+    [
+        (circuit_voxel("in"), Coord::new(0, 0, 0)),
+        (circuit_voxel("out"), Coord::new(0, 0, 1)),
+    ].into()
+}
+
+/// Get the state of a logic gate
+fn get_gate_data(block: &Block) -> Option<&VoxelGate> {
+    match block {
+        Block::ANDGate(data) | Block::ORGate(data) | Block::XORGate(data) |
+        Block::NANDGate(data) | Block::NORGate(data) | Block::XNORGate(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Get the mutable state of a logic gate
+fn get_gate_data_mut(block: &mut Block) -> Option<&mut VoxelGate> {
+    match block {
+        Block::ANDGate(data) | Block::ORGate(data) | Block::XORGate(data) |
+        Block::NANDGate(data) | Block::NORGate(data) | Block::XNORGate(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Logic gate computing its output from 2-8 inputs
+pub struct Gate(pub fn(&[PowerState]) -> PowerState);
+
+impl BlockKind for Gate
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        get_gate_data(block)
+            .map(|data| gate_structure(&gate_inputs(data.inputs)))
+            .unwrap_or_default()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        get_gate_data(block)
+            .map(|data| (circuit_voxel("out"), Signal::from(data.powered)))
+            .into_iter()
+            .collect()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Some(data) = get_gate_data_mut(block) {
+            let states = gate_inputs(data.inputs)
+                .iter()
+                .map(|e| inputs.get(e).is_some_and(|e| *e != 0))
+                .collect::<Vec<PowerState>>();
+
+            data.powered = (self.0)(&states);
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Get the state of a single-input gate
+fn get_unary_gate_data(block: &Block) -> Option<&VoxelPowered> {
+    match block {
+        Block::NOTGate(data) | Block::Diode(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Get the mutable state of a single-input gate
+fn get_unary_gate_data_mut(block: &mut Block) -> Option<&mut VoxelPowered> {
+    match block {
+        Block::NOTGate(data) | Block::Diode(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Gate computing its output from a single input
+pub struct UnaryGate(pub fn(PowerState) -> PowerState);
+
+impl BlockKind for UnaryGate
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        unary_gate_structure()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        get_unary_gate_data(block)
+            .map(|data| (circuit_voxel("out"), Signal::from(data.powered)))
+            .into_iter()
+            .collect()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Some(data) = get_unary_gate_data_mut(block) {
+            data.powered = (self.0)(input_power(inputs, "in"));
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Block computing its output by looking up its input states in a truth table
+pub struct Lut;
+
+impl BlockKind for Lut
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        match block {
+            Block::Lut(data) => gate_structure(&gate_inputs(data.inputs)),
+            _ => Default::default()
+        }
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Lut(data) => [(circuit_voxel("out"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Lut(data) = block else { return };
+
+        let index = gate_inputs(data.inputs)
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| inputs.get(*e).is_some_and(|e| *e != 0))
+            .fold(0, |index, (i, _)| index | 1 << i);
+
+        data.powered = data.table.lookup(index);
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        match block {
+            Block::Lut(data) => data.table.validate(1 << gate_inputs(data.inputs).len()),
+            _ => Ok(())
+        }
+    }
+}
+
+/// Gate outputting its input delayed by a number of ticks
+pub struct Repeater;
+
+impl BlockKind for Repeater
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        unary_gate_structure()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Repeater(data) => [(circuit_voxel("out"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Repeater(data) = block else { return };
+
+        // The output lags one tick behind the newest input on its own, the history holds the remaining ticks
+        data.history.push_back(input_power(inputs, "in"));
+        while data.history.len() > data.delay.clamp(1, MAX_REPEATER_DELAY) as usize - 1 {
+            data.powered = data.history.pop_front().unwrap();
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        match block {
+            Block::Repeater(data) if !(1..=MAX_REPEATER_DELAY).contains(&data.delay) => {
+                Err(format!("repeater delay must be between 1 and {} ticks", MAX_REPEATER_DELAY))
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+/// Block comparing the signal strengths of its inputs `a` and `b` (see `ComparatorMode`)
+pub struct Comparator;
+
+impl BlockKind for Comparator
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        gate_structure(&[circuit_voxel("a"), circuit_voxel("b")])
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Comparator(data) => [(circuit_voxel("out"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        let Block::Comparator(data) = block else { return };
+
+        let (a, b) = (input_signal(inputs, "a").min(MAX_STRENGTH), input_signal(inputs, "b").min(MAX_STRENGTH));
+        let value = match data.mode {
+            ComparatorMode::Compare => if a >= b { a } else { 0 },
+            ComparatorMode::Subtract => a.saturating_sub(b),
+        };
+
+        // Digital wires only carry whether the output is powered
+        data.value = if context.analog { value } else { Signal::from(value != 0) };
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn analog_outputs(&self, _block: &Block) -> bool {
+        true
+    }
+}
+
+/// Block outputting the signal strength of its input `in` multiplied by its gain on `out`
+pub struct Amplifier;
+
+impl BlockKind for Amplifier
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        unary_gate_structure()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Amplifier(data) => [(circuit_voxel("out"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        let Block::Amplifier(data) = block else { return };
+
+        let input = input_signal(inputs, "in");
+        data.value = if context.analog {
+            (input.min(MAX_STRENGTH) * Signal::from(data.gain.max(1))).min(MAX_STRENGTH)
+        } else {
+            // Digital wires only carry whether the output is powered
+            Signal::from(input != 0)
+        };
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn analog_outputs(&self, _block: &Block) -> bool {
+        true
+    }
+}
+
+/// Function of a selector block
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectorMode
+{
+    /// Outputs the signal of input `in_<sel>` on `out`
+    Mux,
+    /// Outputs the signal of input `in` on `out_<sel>`
+    Demux,
+    /// Powers `out_0`, `out_1`, ... with the binary index of the highest powered input and powers `valid` if any is
+    Encoder,
+    /// Powers output `out_<in>`, where `in_0`, `in_1`, ... form a binary index
+    Decoder,
+}
+
+/// Get the state of a selector block
+fn get_selector_data(block: &Block) -> Option<&VoxelSelector> {
+    match block {
+        Block::Mux(data) | Block::Demux(data) | Block::Encoder(data) | Block::Decoder(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Get the mutable state of a selector block
+fn get_selector_data_mut(block: &mut Block) -> Option<&mut VoxelSelector> {
+    match block {
+        Block::Mux(data) | Block::Demux(data) | Block::Encoder(data) | Block::Decoder(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Get the binary index formed by the powered terminals `name_0` (least significant bit), `name_1`, ...
+fn binary_index(inputs: &HashMap<VoxelID, Signal>, name: &str, bits: u8) -> usize {
+    (0..bits)
+        .filter(|i| inputs.get(&indexed_voxel(name, *i)).is_some_and(|e| *e != 0))
+        .fold(0, |index, i| index | 1 << i)
+}
+
+/// Multiplexer, demultiplexer, encoder or decoder laid out like a gate
+pub struct Selector(pub SelectorMode);
+
+impl Selector
+{
+    /// Get the input and output terminals of a selector with the given number of select lines
+    pub fn terminals(&self, select_bits: u8) -> (Vec<VoxelID>, Vec<VoxelID>) {
+        let bits = select_bits.clamp(MIN_SELECT_BITS, MAX_SELECT_BITS);
+        let lines = |name| (0..1 << bits).map(|i| indexed_voxel(name, i)).collect::<Vec<_>>();
+        let selects = |name| (0..bits).map(|i| indexed_voxel(name, i)).collect::<Vec<_>>();
+
+        match self.0 {
+            SelectorMode::Mux => ([lines("in"), selects("sel")].concat(), vec![circuit_voxel("out")]),
+            SelectorMode::Demux => ([vec![circuit_voxel("in")], selects("sel")].concat(), lines("out")),
+            SelectorMode::Encoder => (lines("in"), [selects("out"), vec![circuit_voxel("valid")]].concat()),
+            SelectorMode::Decoder => (selects("in"), lines("out")),
+        }
+    }
+}
+
+impl BlockKind for Selector
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        get_selector_data(block)
+            .map(|data| {
+                let (inputs, outputs) = self.terminals(data.select_bits);
+                gate_structure_with(&inputs, &outputs)
+            })
+            .unwrap_or_default()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        let Some(data) = get_selector_data(block) else { return Default::default() };
+
+        self.terminals(data.select_bits).1
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| (e, data.outputs.get(i).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Some(data) = get_selector_data_mut(block) else { return };
+
+        let bits = data.select_bits.clamp(MIN_SELECT_BITS, MAX_SELECT_BITS);
+        let lines = 1 << bits;
+
+        data.outputs = match self.0 {
+            SelectorMode::Mux => {
+                let selected = binary_index(inputs, "sel", bits) as u8;
+                vec![inputs.get(&indexed_voxel("in", selected)).copied().unwrap_or(0)]
+            }
+            SelectorMode::Demux => {
+                let (selected, signal) = (binary_index(inputs, "sel", bits), input_signal(inputs, "in"));
+                (0..lines).map(|i| if i == selected { signal } else { 0 }).collect()
+            }
+            SelectorMode::Encoder => {
+                let highest = (0..lines as u8).rev().find(|i| inputs.get(&indexed_voxel("in", *i)).is_some_and(|e| *e != 0));
+                let index = highest.unwrap_or(0);
+
+                (0..bits)
+                    .map(|i| Signal::from(index >> i & 1))
+                    .chain([Signal::from(highest.is_some())])
+                    .collect()
+            }
+            SelectorMode::Decoder => {
+                let selected = binary_index(inputs, "in", bits);
+                (0..lines).map(|i| Signal::from(i == selected)).collect()
+            }
+        };
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+#[test]
+fn gate_inputs_test()
+{
+    use crate::block::{Orient, VoxelWire};
+    use crate::scene::Scene;
+
+    for orientation in Orient::all() {
+        // Direction pointing out of the back of the gate in this orientation
+        let not_gate = Block::NOTGate(Default::default()).get_global_structure(Coord::new(0, 0, 0), orientation);
+        let back = not_gate[&circuit_voxel("in")] - not_gate[&circuit_voxel("out")];
+
+        for (gate, states, expected) in [
+            (Block::ANDGate(VoxelGate { inputs: 4, powered: false }), [true, true, true, true], true),
+            (Block::ANDGate(VoxelGate { inputs: 4, powered: false }), [true, true, false, true], false),
+            (Block::NORGate(VoxelGate { inputs: 4, powered: false }), [false, false, false, false], true),
+            (Block::XORGate(VoxelGate { inputs: 4, powered: false }), [true, false, true, true], true),
+            (Block::XNORGate(VoxelGate { inputs: 4, powered: false }), [true, false, true, true], false),
+        ] {
+            let mut scene = Scene::default();
+            let structure = gate.get_global_structure(Coord::new(0, 0, 0), orientation);
+            assert_eq!(structure.len(), 7 * 3);
+
+            scene.add_block(gate, Coord::new(0, 0, 0), orientation).unwrap();
+            for (i, state) in states.into_iter().enumerate() {
+                let position = structure[&indexed_voxel("in", i as u8)] + back;
+                scene.add_block(Block::Toggle(VoxelPowered { powered: state }), position, Default::default()).unwrap();
+            }
+            let wire = scene.add_block(Block::Wire(VoxelWire::default()), structure[&circuit_voxel("out")] - back, Default::default()).unwrap();
+
+            scene.simulate_tick();
+            assert_eq!(scene.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: expected, ..Default::default() }), "{:?} {:?}", orientation, states);
+        }
+    }
+}
+
+#[test]
+fn lut_test()
+{
+    use crate::block::{TruthTable, VoxelLut, VoxelWire};
+    use crate::scene::Scene;
+
+    // Both forms of truth table go through the protocol encoding
+    let xor = Block::Lut(VoxelLut { inputs: 2, table: TruthTable::Bits("0110".to_string()), powered: false });
+    let json = r#"{"block":"lut","data":{"inputs":2,"table":"0110","powered":false}}"#;
+    assert_eq!(serde_json::to_string(&xor).unwrap(), json);
+    assert_eq!(serde_json::from_str::<Block>(json).unwrap(), xor);
+
+    let majority = Block::Lut(VoxelLut { inputs: 3, table: TruthTable::Minterms(vec![3, 5, 6, 7]), powered: false });
+    let json = r#"{"block":"lut","data":{"inputs":3,"table":[3,5,6,7],"powered":false}}"#;
+    assert_eq!(serde_json::to_string(&majority).unwrap(), json);
+    assert_eq!(serde_json::from_str::<Block>(json).unwrap(), majority);
+
+    for (table, states, expected) in [
+        (majority.clone(), [true, false, true], true),
+        (majority.clone(), [false, false, true], false),
+        (Block::Lut(VoxelLut { inputs: 3, table: TruthTable::Bits("00000010".to_string()), powered: false }), [false, true, true], true),
+        (Block::Lut(VoxelLut { inputs: 3, table: TruthTable::Bits("0101".to_string()), powered: false }), [true, true, true], false),
+    ] {
+        let mut scene = Scene::default();
+        scene.add_block(table, Coord::new(0, 0, 0), Default::default()).unwrap();
+        for (i, state) in states.into_iter().enumerate() {
+            scene.add_block(Block::Toggle(VoxelPowered { powered: state }), Coord::new(2 * i as i32 - 2, 0, -2), Default::default()).unwrap();
+        }
+        let wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(0, 0, 2), Default::default()).unwrap();
+
+        scene.simulate_tick();
+        assert_eq!(scene.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: expected, ..Default::default() }), "{:?}", states);
+    }
+    // Tables must match the number of inputs when placed
+    assert!(xor.clone().prepare().is_ok());
+    assert!(majority.clone().prepare().is_ok());
+    for table in [
+        TruthTable::Bits("0120".to_string()),
+        TruthTable::Bits("011".to_string()),
+        TruthTable::Bits("01100110".to_string()),
+        TruthTable::Minterms(vec![1, 4]),
+    ] {
+        let mut scene = Scene::default();
+        assert!(scene.place_block(Block::Lut(VoxelLut { inputs: 2, table: table.clone(), powered: false }), Coord::new(0, 0, 0), Default::default()).is_err(), "{:?}", table);
+    }
+}
+
+#[test]
+fn repeater_test()
+{
+    use crate::block::VoxelRepeater;
+    use crate::scene::{Scene, SceneData};
+
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    let repeater = scene.add_block(Block::Repeater(VoxelRepeater { delay: 3, ..Default::default() }), Coord::new(0, 0, 0), Default::default()).unwrap();
+
+    scene.simulate_tick();
+    scene.update_block(toggle, Block::Toggle(VoxelPowered { powered: false }));
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(repeater).unwrap().2, Block::Repeater(VoxelRepeater {
+        delay: 3,
+        history: [true, false].into(),
+        powered: false,
+    }));
+
+    // The history in flight survives a save/load round trip
+    let data = serde_json::to_string(&SceneData::from(scene)).unwrap();
+    let mut scene = Scene::from(serde_json::from_str::<SceneData>(&data).unwrap());
+
+    let mut outputs = vec![];
+    for _ in 0..3 {
+        scene.simulate_tick();
+        outputs.push(scene.get_block(repeater).unwrap().2.get_circuit_power());
+    }
+    assert_eq!(outputs, [true, false, false]);
+
+    // Delays are bounded, since the history grows with them
+    for delay in [0, MAX_REPEATER_DELAY + 1, u32::MAX] {
+        assert!(scene.place_block(Block::Repeater(VoxelRepeater { delay, ..Default::default() }), Coord::new(5, 0, 0), Default::default()).is_err());
+    }
+    assert!(scene.place_block(Block::Repeater(VoxelRepeater { delay: MAX_REPEATER_DELAY, ..Default::default() }), Coord::new(5, 0, 0), Default::default()).is_ok());
+}
+
+#[test]
+fn selector_test()
+{
+    let evaluate = |mode: SelectorMode, select_bits: u8, inputs: &[(VoxelID, Signal)]| -> HashMap<VoxelID, Signal> {
+        let mut block = match mode {
+            SelectorMode::Mux => Block::Mux(VoxelSelector { select_bits, ..Default::default() }),
+            SelectorMode::Demux => Block::Demux(VoxelSelector { select_bits, ..Default::default() }),
+            SelectorMode::Encoder => Block::Encoder(VoxelSelector { select_bits, ..Default::default() }),
+            SelectorMode::Decoder => Block::Decoder(VoxelSelector { select_bits, ..Default::default() }),
+        };
+        block.kind().evaluate(&mut block, &inputs.iter().cloned().collect(), &Context::default());
+        block.kind().outputs(&block)
+    };
+    let powered = |outputs: &HashMap<VoxelID, Signal>| -> Vec<VoxelID> {
+        let mut powered = outputs.iter().filter(|e| *e.1 != 0).map(|e| e.0.clone()).collect::<Vec<_>>();
+        powered.sort();
+        powered
+    };
+
+    // A 4-to-1 multiplexer passes the whole signal of the selected input (e.g. a bus value)
+    let mux = evaluate(SelectorMode::Mux, 2, &[
+        (indexed_voxel("in", 0), 7), (indexed_voxel("in", 2), 42), (indexed_voxel("sel", 1), 1),
+    ]);
+    assert_eq!(mux, [(circuit_voxel("out"), 42)].into());
+    assert_eq!(Selector(SelectorMode::Mux).terminals(2).0.len(), 6);
+
+    let demux = evaluate(SelectorMode::Demux, 2, &[(circuit_voxel("in"), 5), (indexed_voxel("sel", 0), 1), (indexed_voxel("sel", 1), 1)]);
+    assert_eq!(demux.len(), 4);
+    assert_eq!(demux[&indexed_voxel("out", 3)], 5);
+    assert_eq!(powered(&demux), [indexed_voxel("out", 3)]);
+
+    // The encoder prioritises the highest powered input
+    let encoder = evaluate(SelectorMode::Encoder, 3, &[(indexed_voxel("in", 1), 1), (indexed_voxel("in", 6), 1)]);
+    assert_eq!(powered(&encoder), [indexed_voxel("out", 1), indexed_voxel("out", 2), circuit_voxel("valid")]);
+    assert!(powered(&evaluate(SelectorMode::Encoder, 3, &[])).is_empty());
+
+    let decoder = evaluate(SelectorMode::Decoder, 3, &[(indexed_voxel("in", 0), 1), (indexed_voxel("in", 2), 1)]);
+    assert_eq!(decoder.len(), 8);
+    assert_eq!(powered(&decoder), [indexed_voxel("out", 5)]);
+}
+
+#[test]
+fn comparator_test()
+{
+    use crate::block::{VoxelAmplifier, VoxelComparator};
+    use crate::scene::Scene;
+
+    let analog = Context { analog: true, ..Default::default() };
+    let compare = |mode: ComparatorMode, a: Signal, b: Signal| -> Signal {
+        let mut block = Block::Comparator(VoxelComparator { mode, value: 0 });
+        let inputs = [(circuit_voxel("a"), a), (circuit_voxel("b"), b)].into();
+        block.kind().evaluate(&mut block, &inputs, &analog);
+        block.kind().outputs(&block)[&circuit_voxel("out")]
+    };
+
+    assert_eq!(compare(ComparatorMode::Compare, 9, 4), 9);
+    assert_eq!(compare(ComparatorMode::Compare, 4, 4), 4);
+    assert_eq!(compare(ComparatorMode::Compare, 3, 4), 0);
+    assert_eq!(compare(ComparatorMode::Subtract, 9, 4), 5);
+    assert_eq!(compare(ComparatorMode::Subtract, 3, 4), 0);
+    assert_eq!(compare(ComparatorMode::Compare, 200, 15), MAX_STRENGTH);
+
+    // Amplified strengths are capped
+    let mut block = Block::Amplifier(VoxelAmplifier { gain: 3, value: 0 });
+    block.kind().evaluate(&mut block, &[(circuit_voxel("in"), 6)].into(), &analog);
+    assert_eq!(block, Block::Amplifier(VoxelAmplifier { gain: 3, value: MAX_STRENGTH }));
+
+    // In digital worlds both only output whether they are powered
+    let mut block = Block::Amplifier(VoxelAmplifier { gain: 2, value: 0 });
+    block.kind().evaluate(&mut block, &[(circuit_voxel("in"), 1)].into(), &Context::default());
+    assert_eq!(block, Block::Amplifier(VoxelAmplifier { gain: 2, value: 1 }));
+    let mut block = Block::Comparator(VoxelComparator { mode: ComparatorMode::Compare, value: 0 });
+    block.kind().evaluate(&mut block, &[(circuit_voxel("a"), 9), (circuit_voxel("b"), 4)].into(), &Context::default());
+    assert_eq!(block, Block::Comparator(VoxelComparator { mode: ComparatorMode::Compare, value: 1 }));
+
+    // A digital amplifier powers the wire it drives
+    let mut scene = Scene::default();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    scene.add_block(Block::Amplifier(VoxelAmplifier { gain: 2, value: 0 }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let wire = scene.add_block(Block::Wire(Default::default()), Coord::new(0, 0, 2), Default::default()).unwrap();
+    scene.simulate_tick();
+    scene.simulate_tick();
+    assert!(scene.get_block(wire).unwrap().2.get_circuit_power());
+}
@@ -0,0 +1,963 @@
+use std::collections::HashMap;
+
+use cgmath::Zero;
+
+use crate::block::{BLACK, Block, circuit_voxel, ColorSource, indexed_voxel, MAX_GATE_INPUTS, MIN_COLOR_INPUTS, Retrigger, Signal, VoxelDisplay, VoxelID};
+use crate::framebuffer::MAX_FRAMEBUFFER_SIZE;
+use crate::grid::Coord;
+use crate::kinds::gate::{gate_faces, gate_structure_with};
+use crate::registry::{BlockKind, Context, input_power, input_signal, InteractAction};
+
+/// Empty space
+pub struct Air;
+
+impl BlockKind for Air
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        Default::default()
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, _block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {}
+}
+
+/// Plain building block, not part of the circuit
+pub struct Solid;
+
+impl BlockKind for Solid
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [("block".to_string(), Coord::zero())].into()
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, _block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {}
+}
+
+/// User-controlled switch
+pub struct Toggle;
+
+impl BlockKind for Toggle
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("toggle"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Toggle(data) => [(circuit_voxel("toggle"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, _block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        // Toggle is an external independent source therefore its state can only be modified by the user
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn interact(&self, block: &mut Block, action: &InteractAction) -> Result<(), String> {
+        let Block::Toggle(data) = block else { return Ok(()) };
+
+        match action {
+            InteractAction::Toggle => data.powered = !data.powered,
+            InteractAction::Set(value) => data.powered = *value != 0,
+            _ => return Err(format!("toggles do not support {:?}", action)),
+        }
+
+        Ok(())
+    }
+
+    fn rewrite_action(&self, data: &Block) -> Option<InteractAction> {
+        match data {
+            Block::Toggle(data) => Some(InteractAction::Set(Signal::from(data.powered))),
+            _ => None
+        }
+    }
+}
+
+/// User-controlled push-button, released by the server after a number of ticks
+pub struct Button;
+
+impl BlockKind for Button
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("button"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Button(data) => [(circuit_voxel("button"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Button(data) = block else { return };
+
+        // Pressing the button (again) restarts the press
+        if data.pressed {
+            data.remaining = data.press_ticks.max(1);
+            data.pressed = false;
+        }
+
+        data.powered = data.remaining > 0;
+        data.remaining = data.remaining.saturating_sub(1);
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn interact(&self, block: &mut Block, action: &InteractAction) -> Result<(), String> {
+        let Block::Button(data) = block else { return Ok(()) };
+
+        match action {
+            InteractAction::Press => data.pressed = true,
+            _ => return Err(format!("buttons do not support {:?}", action)),
+        }
+
+        Ok(())
+    }
+
+    fn rewrite_action(&self, data: &Block) -> Option<InteractAction> {
+        matches!(data, Block::Button(data) if data.pressed).then_some(InteractAction::Press)
+    }
+}
+
+/// User-controlled keypad with 16 keys, outputting the last key pressed on `out`
+/// and powering `pressed` for a number of ticks after each press
+pub struct Keypad;
+
+impl BlockKind for Keypad
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        gate_structure_with(&[], &[circuit_voxel("out"), circuit_voxel("pressed")])
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Keypad(data) => [
+                (circuit_voxel("out"), data.value),
+                (circuit_voxel("pressed"), Signal::from(data.powered)),
+            ].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Keypad(data) = block else { return };
+
+        if data.pressed {
+            data.remaining = data.press_ticks.max(1);
+            data.pressed = false;
+        }
+
+        data.powered = data.remaining > 0;
+        data.remaining = data.remaining.saturating_sub(1);
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn interact(&self, block: &mut Block, action: &InteractAction) -> Result<(), String> {
+        let Block::Keypad(data) = block else { return Ok(()) };
+
+        match action {
+            InteractAction::Key(key) if *key < 16 => {
+                data.value = Signal::from(*key);
+                data.pressed = true;
+            }
+            _ => return Err(format!("keypads do not support {:?}", action)),
+        }
+
+        Ok(())
+    }
+
+    fn rewrite_action(&self, data: &Block) -> Option<InteractAction> {
+        match data {
+            Block::Keypad(data) if data.pressed => u8::try_from(data.value).ok().map(InteractAction::Key),
+            _ => None
+        }
+    }
+}
+
+/// User-controlled dial outputting its value on `dial`
+pub struct Dial;
+
+impl BlockKind for Dial
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("dial"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Dial(data) => [(circuit_voxel("dial"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, _block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        // Dial is an external independent source therefore its state can only be modified by the user
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn interact(&self, block: &mut Block, action: &InteractAction) -> Result<(), String> {
+        let Block::Dial(data) = block else { return Ok(()) };
+
+        // Turning the dial past either end wraps around
+        data.value = match action {
+            InteractAction::Set(value) if *value <= data.max => *value,
+            InteractAction::Increment => if data.value >= data.max { 0 } else { data.value + 1 },
+            InteractAction::Decrement => if data.value == 0 { data.max } else { data.value - 1 },
+            _ => return Err(format!("dials do not support {:?}", action)),
+        };
+
+        Ok(())
+    }
+
+    fn rewrite_action(&self, data: &Block) -> Option<InteractAction> {
+        match data {
+            Block::Dial(data) => Some(InteractAction::Set(data.value)),
+            _ => None
+        }
+    }
+}
+
+/// Light showing whether its input is powered
+pub struct Pixel;
+
+impl BlockKind for Pixel
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("pixel"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Block::Pixel(data) = block {
+            data.powered = input_power(inputs, "pixel");
+        }
+    }
+}
+
+/// Get the number of inputs of a color pixel (1 for a switched color)
+fn color_inputs(source: &ColorSource) -> u8 {
+    match source {
+        ColorSource::Switch { .. } => 1,
+        ColorSource::Rgb { inputs } | ColorSource::Palette { inputs, .. } => (*inputs).clamp(MIN_COLOR_INPUTS, MAX_GATE_INPUTS),
+    }
+}
+
+/// Light showing a color chosen by its inputs
+///
+/// A switched color pixel has a single input `pixel`, other color pixels have inputs `in_0`, `in_1`, ...
+/// (`in_0` being the least significant bit) along their back, laid out like a gate
+pub struct ColorPixel;
+
+impl BlockKind for ColorPixel
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        let Block::ColorPixel(data) = block else { return Default::default() };
+
+        match data.source {
+            ColorSource::Switch { .. } => [(circuit_voxel("pixel"), Coord::zero())].into(),
+            _ => {
+                let inputs = (0..color_inputs(&data.source))
+                    .map(|i| indexed_voxel("in", i))
+                    .collect::<Vec<_>>();
+
+                gate_structure_with(&inputs, &[])
+            }
+        }
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::ColorPixel(data) = block else { return };
+
+        let value = (0..color_inputs(&data.source))
+            .filter(|i| inputs.get(&indexed_voxel("in", *i)).is_some_and(|e| *e != 0))
+            .fold(0u32, |value, i| value | 1 << i);
+
+        data.color = match &data.source {
+            ColorSource::Switch { on, off } => if input_power(inputs, "pixel") { *on } else { *off },
+            ColorSource::Rgb { .. } => {
+                // Split the bits between channels, giving any remaining bits to red then green
+                let bits = color_inputs(&data.source) as u32;
+                let (red_bits, green_bits, blue_bits) = (bits.div_ceil(3), (bits + 1) / 3, bits / 3);
+
+                let channel = |shift: u32, bits: u32| {
+                    let max = (1 << bits) - 1;
+                    ((value >> shift & max) * 255 / max) as u8
+                };
+
+                (channel(green_bits + blue_bits, red_bits), channel(blue_bits, green_bits), channel(0, blue_bits), 255)
+            }
+            ColorSource::Palette { palette, .. } => palette.get(value as usize).copied().unwrap_or(BLACK),
+        };
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        // A switched color pixel connects on all sides like a plain pixel
+        match block {
+            Block::ColorPixel(data) if !matches!(data.source, ColorSource::Switch { .. }) => gate_faces(self, block),
+            _ => Default::default()
+        }
+    }
+}
+
+/// Get the name of the voxel showing the given pixel of a display
+pub fn display_voxel(column: u32, row: u32) -> VoxelID {
+    format!("pixel-{}-{}", column, row)
+}
+
+/// Get the index in a display's pixels of the pixel shown by the given voxel
+pub fn display_pixel_index(data: &VoxelDisplay, voxel_id: &str) -> Option<usize> {
+    let (column, row) = voxel_id.strip_prefix("pixel-")?.split_once('-')?;
+    let (column, row) = (column.parse::<u32>().ok()?, row.parse::<u32>().ok()?);
+    let (width, height) = Display::size(data.width, data.height);
+
+    (column < width && row < height).then_some((row * width + column) as usize)
+}
+
+/// Grid of pixels facing -Z, whose pixel at (`x`, `y`) is set to the color `data` (as `0xRRGGBB`) when `strobe` turns on
+///
+/// The inputs are along the back of the bottom row, and rows are numbered from the top
+pub struct Display;
+
+impl Display
+{
+    /// Get the size of a display
+    fn size(width: u32, height: u32) -> (u32, u32) {
+        (width.clamp(1, MAX_FRAMEBUFFER_SIZE), height.clamp(1, MAX_FRAMEBUFFER_SIZE))
+    }
+}
+
+impl BlockKind for Display
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        let Block::Display(data) = block else { return Default::default() };
+        let (width, height) = Display::size(data.width, data.height);
+
+        (0..height)
+            .flat_map(|row| (0..width).map(move |column| (display_voxel(column, row), Coord::new(column as i32, (height - 1 - row) as i32, 0))))
+            .chain(["x", "y", "data", "strobe"]
+                .into_iter()
+                .enumerate()
+                .map(|(i, e)| (circuit_voxel(e), Coord::new(2 * i as i32, 0, -1))))
+            .collect()
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Display(data) = block else { return };
+        let (width, height) = Display::size(data.width, data.height);
+        data.pixels.resize((width * height) as usize, BLACK);
+
+        // Write on the rising edge of the strobe
+        let strobe = input_power(inputs, "strobe");
+        let (x, y) = (input_signal(inputs, "x"), input_signal(inputs, "y"));
+        if strobe && !data.strobe && x < width as Signal && y < height as Signal {
+            let color = input_signal(inputs, "data");
+            data.pixels[(y * width as Signal + x) as usize] = ((color >> 16) as u8, (color >> 8) as u8, color as u8, 255);
+        }
+        data.strobe = strobe;
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        if let Block::Display(data) = block {
+            let (width, height) = Display::size(data.width, data.height);
+            data.pixels.resize((width * height) as usize, BLACK);
+        }
+
+        Ok(())
+    }
+}
+
+/// Segments lit for each hexadecimal digit (segment `a` as bit 0 through segment `g` as bit 6)
+pub const HEX_DIGIT_SEGMENTS: [u8; 16] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07,
+    0x7f, 0x6f, 0x77, 0x7c, 0x39, 0x5e, 0x79, 0x71,
+];
+
+/// Names of the segment inputs of a seven-segment display, in bit order
+const SEGMENTS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "dp"];
+
+/// Seven-segment display with an input for each segment (`a` to `g`) and the decimal point (`dp`), laid out like a gate
+pub struct SevenSegment;
+
+impl BlockKind for SevenSegment
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        gate_structure_with(&SEGMENTS.map(circuit_voxel), &[])
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Block::SevenSegment(data) = block {
+            data.segments = SEGMENTS
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| input_power(inputs, e))
+                .fold(0, |segments, (i, _)| segments | 1 << i);
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Seven-segment display showing the hexadecimal digit given by its inputs `in_0` to `in_3` (`in_0` being the
+/// least significant bit), with a decimal point input `dp`, laid out like a gate
+pub struct HexDigit;
+
+impl BlockKind for HexDigit
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        let inputs = (0..4)
+            .map(|i| indexed_voxel("in", i))
+            .chain([circuit_voxel("dp")])
+            .collect::<Vec<_>>();
+
+        gate_structure_with(&inputs, &[])
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::HexDigit(data) = block else { return };
+
+        data.value = (0..4)
+            .filter(|i| inputs.get(&indexed_voxel("in", *i)).is_some_and(|e| *e != 0))
+            .fold(0, |value, i| value | 1 << i);
+        data.segments = HEX_DIGIT_SEGMENTS[data.value as usize] | if input_power(inputs, "dp") { 0x80 } else { 0 };
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Source powered for `high_ticks` ticks then unpowered for `low_ticks` ticks, repeating from `start_tick`
+/// with its cycle shifted forward by `phase` ticks
+///
+/// When `enable` is set, the clock has an `enable` input behind it and is only powered while that input is
+pub struct Clock;
+
+impl BlockKind for Clock
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        let mut structure: HashMap<VoxelID, Coord> = [(circuit_voxel("clock"), Coord::zero())].into();
+
+        if matches!(block, Block::Clock(data) if data.enable) {
+            structure.insert(circuit_voxel("enable"), Coord::new(0, 0, -1));
+        }
+
+        structure
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Clock(data) => [(circuit_voxel("clock"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        // Clock is an external independent source, so its only (optional) circuit input gates its output
+        let Block::Clock(data) = block else { return };
+
+        let enabled = !data.enable || inputs.get(&circuit_voxel("enable")).is_some_and(|e| *e != 0);
+        let period = u64::from(data.high_ticks) + u64::from(data.low_ticks);
+
+        data.powered = if data.start_tick > context.ticks || !enabled || period == 0 {
+            false
+        } else {
+            (u64::from(context.ticks - data.start_tick) + u64::from(data.phase)) % period < u64::from(data.high_ticks)
+        };
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        match block {
+            Block::Clock(data) if data.enable => [(circuit_voxel("enable"), vec![Coord::new(0, 0, -1)])].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        let Block::Clock(data) = block else { return Ok(()) };
+
+        if data.high_ticks == 0 {
+            return Err("clock must be powered for at least one tick".to_string());
+        }
+        if u64::from(data.phase) >= u64::from(data.high_ticks) + u64::from(data.low_ticks) {
+            return Err(format!("clock phase {} is not within its period of {} ticks", data.phase, u64::from(data.high_ticks) + u64::from(data.low_ticks)));
+        }
+
+        Ok(())
+    }
+}
+
+/// Source powered from `start_tick` for `pulse_ticks` ticks
+pub struct Pulse;
+
+impl BlockKind for Pulse
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("pulse"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Pulse(data) => [(circuit_voxel("pulse"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        // Pulse is an external independent source and therefore has no circuit inputs,
+        // but it does have a circuit output value based on the input state
+        if let Block::Pulse(data) = block {
+            data.powered = data.start_tick + data.pulse_ticks >= context.ticks;
+        }
+    }
+}
+
+/// Pulse generator with input `trigger` at the back and output `out` at the front,
+/// powered for a number of ticks after each rising edge of its trigger
+pub struct Monostable;
+
+impl BlockKind for Monostable
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [
+            (circuit_voxel("trigger"), Coord::new(0, 0, 0)),
+            (circuit_voxel("out"), Coord::new(0, 0, 1)),
+        ].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Monostable(data) => [(circuit_voxel("out"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Monostable(data) = block else { return };
+
+        // Timing is kept as the ticks left in the pulse rather than an absolute tick, so it is unaffected by saving
+        let trigger = input_power(inputs, "trigger");
+        if trigger && !data.trigger && (data.remaining == 0 || data.retrigger == Retrigger::Extend) {
+            data.remaining = data.pulse_ticks.max(1);
+        }
+
+        data.trigger = trigger;
+        data.powered = data.remaining > 0;
+        data.remaining = data.remaining.saturating_sub(1);
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        match block {
+            Block::Monostable(data) if data.pulse_ticks == 0 => Err("pulse must last at least one tick".to_string()),
+            _ => Ok(())
+        }
+    }
+}
+
+/// Source of pseudo-random bits, with an optional `clk` input behind it
+pub struct Random;
+
+impl Random
+{
+    /// Get the initial generator state for a seed (xorshift cannot leave a zero state, so a zero seed is remapped)
+    pub fn initial_state(seed: u64) -> u64 {
+        if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed }
+    }
+
+    /// Advance a xorshift64 generator state
+    pub fn next_state(mut state: u64) -> u64 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+}
+
+impl BlockKind for Random
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        let mut structure: HashMap<VoxelID, Coord> = [(circuit_voxel("random"), Coord::zero())].into();
+
+        if matches!(block, Block::Random(data) if data.clocked) {
+            structure.insert(circuit_voxel("clk"), Coord::new(0, 0, -1));
+        }
+
+        structure
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Random(data) => [(circuit_voxel("random"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Random(data) = block else { return };
+
+        let clock = input_power(inputs, "clk");
+        if !data.clocked || clock && !data.clock {
+            data.state = Random::next_state(if data.state == 0 { Random::initial_state(data.seed) } else { data.state });
+            data.powered = data.state & 1 == 1;
+        }
+
+        data.clock = clock;
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        match block {
+            Block::Random(data) if data.clocked => [(circuit_voxel("clk"), vec![Coord::new(0, 0, -1)])].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        if let Block::Random(data) = block {
+            if data.state == 0 {
+                data.state = Random::initial_state(data.seed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reject channel names which cannot be listed or typed by users
+fn validate_channel(block: &Block) -> Result<(), String> {
+    match block {
+        Block::Transmitter(data) | Block::Receiver(data) if data.channel.trim().is_empty() => Err("channel name must not be empty".to_string()),
+        _ => Ok(())
+    }
+}
+
+/// Wireless sink broadcasting the signal at its `in` input to all receivers on its channel
+pub struct Transmitter;
+
+impl BlockKind for Transmitter
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("in"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        // Only kept for display, receivers are driven from the same inputs through `Context::channels`
+        if let Block::Transmitter(data) = block {
+            data.signal = input_signal(inputs, "in");
+        }
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        validate_channel(block)
+    }
+}
+
+/// Wireless source outputting the signal broadcast on its channel during the last tick
+pub struct Receiver;
+
+impl BlockKind for Receiver
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("out"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Receiver(data) => [(circuit_voxel("out"), data.signal)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        if let Block::Receiver(data) = block {
+            data.signal = context.channels.get(&data.channel).copied().unwrap_or(0);
+        }
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        validate_channel(block)
+    }
+}
+
+#[test]
+fn color_pixel_test()
+{
+    use crate::block::{Color, VoxelColorPixel, WHITE};
+
+    let evaluate = |source: ColorSource, inputs: &[(&str, Signal)]| -> Color {
+        let mut block = Block::ColorPixel(VoxelColorPixel { source, ..Default::default() });
+        let inputs = inputs.iter().map(|(name, e)| (circuit_voxel(name), *e)).collect();
+        block.kind().evaluate(&mut block, &inputs, &Context::default());
+
+        let Block::ColorPixel(data) = block else { unreachable!() };
+        data.color
+    };
+
+    assert_eq!(evaluate(Default::default(), &[("pixel", 1)]), WHITE);
+    assert_eq!(evaluate(ColorSource::Switch { on: (255, 0, 0, 255), off: (0, 0, 255, 128) }, &[]), (0, 0, 255, 128));
+
+    // RGB bits, most significant first
+    assert_eq!(evaluate(ColorSource::Rgb { inputs: 3 }, &[("in_2", 1), ("in_0", 1)]), (255, 0, 255, 255));
+    assert_eq!(evaluate(ColorSource::Rgb { inputs: 8 }, &[("in_7", 1), ("in_6", 1), ("in_5", 1), ("in_2", 1), ("in_0", 1)]), (255, 36, 85, 255));
+
+    let palette = ColorSource::Palette { inputs: 3, palette: vec![BLACK, WHITE, (1, 2, 3, 4)] };
+    assert_eq!(evaluate(palette.clone(), &[("in_1", 1)]), (1, 2, 3, 4));
+    assert_eq!(evaluate(palette, &[("in_2", 1)]), BLACK);
+
+    // The layout follows the number of inputs
+    let block = Block::ColorPixel(VoxelColorPixel { source: ColorSource::Rgb { inputs: 6 }, ..Default::default() });
+    assert_eq!(block.get_structure().keys().filter(|e| e.starts_with(&circuit_voxel("in"))).count(), 6);
+
+    let json = r#"{"block":"color_pixel","data":{"source":{"mode":"palette","inputs":3,"palette":[[1,2,3,4]]},"color":[0,0,0,255]}}"#;
+    assert_eq!(serde_json::to_string(&serde_json::from_str::<Block>(json).unwrap()).unwrap(), json);
+}
+
+#[test]
+fn segment_display_test()
+{
+    use crate::block::{VoxelHexDigit, VoxelSevenSegment};
+
+    let evaluate = |mut block: Block, inputs: &[&str]| -> Block {
+        let inputs = inputs.iter().map(|name| (circuit_voxel(name), 1)).collect();
+        block.kind().evaluate(&mut block, &inputs, &Context::default());
+        block
+    };
+
+    assert_eq!(evaluate(Block::SevenSegment(Default::default()), &["a", "c", "g", "dp"]), Block::SevenSegment(VoxelSevenSegment { segments: 0b1100_0101 }));
+    assert_eq!(evaluate(Block::HexDigit(Default::default()), &["in_0", "in_1", "in_3"]), Block::HexDigit(VoxelHexDigit { value: 0xb, segments: 0x7c }));
+    assert_eq!(evaluate(Block::HexDigit(Default::default()), &["dp"]), Block::HexDigit(VoxelHexDigit { value: 0, segments: 0xbf }));
+
+    // Inputs are laid out along the back like those of a gate
+    let structure = Block::SevenSegment(Default::default()).get_structure();
+    assert_eq!(structure[&circuit_voxel("a")], Coord::new(-7, 0, -1));
+    assert_eq!(structure[&circuit_voxel("dp")], Coord::new(7, 0, -1));
+    assert_eq!(Block::HexDigit(Default::default()).get_structure()[&circuit_voxel("dp")], Coord::new(4, 0, -1));
+}
+
+#[test]
+fn button_test()
+{
+    use crate::block::{VoxelButton, VoxelWire};
+    use crate::scene::Scene;
+
+    let mut scene = Scene::default();
+    let button = scene.add_block(Block::Button(VoxelButton { press_ticks: 3, ..Default::default() }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(0, 0, 1), Default::default()).unwrap();
+
+    let mut press = scene.get_block(button).unwrap().2;
+    if let Block::Button(data) = &mut press {
+        data.pressed = true;
+    }
+    scene.update_block(button, press);
+
+    // The button releases itself after its press duration
+    let mut powered = vec![];
+    for _ in 0..5 {
+        scene.simulate_tick();
+        powered.push(scene.get_block(wire).unwrap().2.get_circuit_power());
+    }
+    assert_eq!(powered, [true, true, true, false, false]);
+    assert_eq!(scene.get_block(button).unwrap().2, Block::Button(VoxelButton { press_ticks: 3, pressed: false, remaining: 0, powered: false }));
+}
+
+#[test]
+fn interact_test()
+{
+    use crate::block::{VoxelButton, VoxelClock, VoxelDial, VoxelKeypad, VoxelPowered};
+    use crate::scene::Scene;
+
+    assert_eq!(serde_json::from_str::<InteractAction>(r#""toggle""#).unwrap(), InteractAction::Toggle);
+    assert_eq!(serde_json::from_str::<InteractAction>(r#"{"key": 7}"#).unwrap(), InteractAction::Key(7));
+
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: false }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let button = scene.add_block(Block::Button(VoxelButton { press_ticks: 2, ..Default::default() }), Coord::new(2, 0, 0), Default::default()).unwrap();
+    let keypad = scene.add_block(Block::Keypad(VoxelKeypad { press_ticks: 1, ..Default::default() }), Coord::new(5, 0, 0), Default::default()).unwrap();
+    let dial = scene.add_block(Block::Dial(VoxelDial { max: 9, value: 0 }), Coord::new(8, 0, 0), Default::default()).unwrap();
+    let clock = scene.add_block(Block::Clock(VoxelClock { high_ticks: 1, low_ticks: 3, ..Default::default() }), Coord::new(10, 0, 0), Default::default()).unwrap();
+
+    assert_eq!(scene.interact(toggle, &InteractAction::Toggle), Ok(Block::Toggle(VoxelPowered { powered: true })));
+    assert!(scene.interact(toggle, &InteractAction::Press).is_err());
+    assert!(scene.interact(button, &InteractAction::Press).is_ok());
+    assert!(scene.interact(keypad, &InteractAction::Key(16)).is_err());
+    assert!(scene.interact(keypad, &InteractAction::Key(12)).is_ok());
+    assert_eq!(scene.interact(dial, &InteractAction::Decrement), Ok(Block::Dial(VoxelDial { max: 9, value: 9 })));
+    assert!(scene.interact(dial, &InteractAction::Set(10)).is_err());
+    assert!(scene.interact(clock, &InteractAction::Toggle).is_err());
+
+    scene.simulate_tick();
+    assert!(scene.get_block(button).unwrap().2.get_circuit_power());
+    assert_eq!(scene.get_block(keypad).unwrap().2, Block::Keypad(VoxelKeypad { press_ticks: 1, pressed: false, remaining: 0, value: 12, powered: true }));
+
+    // Clients can rewrite the state of interactive blocks only, as their interactions would
+    assert!(scene.check_rewrite(toggle, &Block::Toggle(VoxelPowered { powered: false })).is_ok());
+    assert!(scene.check_rewrite(toggle, &Block::Dial(VoxelDial::default())).is_err());
+    assert!(scene.check_rewrite(dial, &Block::Dial(VoxelDial { max: 9, value: 4 })).is_ok());
+    assert!(scene.check_rewrite(dial, &Block::Dial(VoxelDial { max: 9, value: 10 })).is_err());
+    assert!(scene.check_rewrite(dial, &Block::Dial(VoxelDial { max: 100, value: 50 })).is_err());
+    let Block::Button(pressed) = scene.get_block(button).unwrap().2 else { unreachable!() };
+    assert!(scene.check_rewrite(button, &Block::Button(VoxelButton { pressed: true, ..pressed.clone() })).is_ok());
+    assert!(scene.check_rewrite(button, &Block::Button(VoxelButton { remaining: u32::MAX, ..pressed.clone() })).is_err());
+    assert!(scene.check_rewrite(button, &Block::Button(VoxelButton { pressed: true, remaining: u32::MAX, ..pressed })).is_err());
+    let Block::Keypad(keypad_data) = scene.get_block(keypad).unwrap().2 else { unreachable!() };
+    assert!(scene.check_rewrite(keypad, &Block::Keypad(VoxelKeypad { pressed: true, value: 3, ..keypad_data.clone() })).is_ok());
+    assert!(scene.check_rewrite(keypad, &Block::Keypad(VoxelKeypad { pressed: true, value: 16, ..keypad_data.clone() })).is_err());
+    assert!(scene.check_rewrite(keypad, &Block::Keypad(VoxelKeypad { press_ticks: 1000, ..keypad_data })).is_err());
+    assert!(scene.check_rewrite(clock, &Block::Clock(VoxelClock { high_ticks: 1, low_ticks: 3, powered: true, ..Default::default() })).is_err());
+    assert!(scene.check_rewrite(clock, &scene.get_block(clock).unwrap().2).is_ok());
+}
+
+#[test]
+fn clock_test()
+{
+    use crate::block::{VoxelClock, VoxelPowered};
+    use crate::scene::Scene;
+
+    let clock = Block::Clock(VoxelClock { high_ticks: 2, low_ticks: 1, phase: 1, enable: true, ..Default::default() });
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: false }), Coord::new(0, 0, -2), Default::default()).unwrap();
+    let clock = scene.place_block(clock, Coord::new(0, 0, 0), Default::default()).unwrap();
+
+    let mut powered = vec![];
+    for i in 0..8 {
+        if i == 2 {
+            scene.update_block(toggle, Block::Toggle(VoxelPowered { powered: true }));
+        }
+        scene.simulate_tick();
+        powered.push(scene.get_block(clock).unwrap().2.get_circuit_power());
+    }
+    // Output is gated until the enable input is powered, while the cycle of two ticks on and one off keeps running
+    assert_eq!(powered, [false, false, true, false, true, true, false, true]);
+
+    // Invalid timing is rejected when placed
+    assert!(scene.place_block(Block::Clock(VoxelClock { high_ticks: 0, low_ticks: 4, ..Default::default() }), Coord::new(5, 0, 0), Default::default()).is_err());
+    assert!(scene.place_block(Block::Clock(VoxelClock { high_ticks: 1, low_ticks: 1, phase: 2, ..Default::default() }), Coord::new(5, 0, 0), Default::default()).is_err());
+
+    // Older scenes describe a single-tick pulse every `rate` ticks
+    let legacy: Block = serde_json::from_str(r#"{"block": "clock", "data": {"rate": 4, "start_tick": 2, "powered": false}}"#).unwrap();
+    assert_eq!(legacy, Block::Clock(VoxelClock { high_ticks: 1, low_ticks: 3, start_tick: 2, ..Default::default() }));
+}
+
+#[test]
+fn monostable_test()
+{
+    use crate::block::VoxelMonostable;
+
+    let run = |retrigger: Retrigger, triggers: &[bool]| -> Vec<bool> {
+        let mut block = Block::Monostable(VoxelMonostable { pulse_ticks: 3, retrigger, ..Default::default() });
+
+        triggers
+            .iter()
+            .map(|e| {
+                let inputs = [(circuit_voxel("trigger"), Signal::from(*e))].into();
+                block.kind().evaluate(&mut block, &inputs, &Context::default());
+                block.get_circuit_power()
+            })
+            .collect()
+    };
+
+    // A held trigger only fires once, and the block can fire again after its pulse
+    let triggers = [true, true, true, true, true, false, true, false, false, false];
+    assert_eq!(run(Retrigger::Extend, &triggers), [true, true, true, false, false, false, true, true, true, false]);
+
+    // Triggering during a pulse either restarts it or is ignored
+    let triggers = [true, false, true, false, false, false, false];
+    assert_eq!(run(Retrigger::Extend, &triggers), [true, true, true, true, true, false, false]);
+    assert_eq!(run(Retrigger::Ignore, &triggers), [true, true, true, false, false, false, false]);
+
+    let mut block = Block::Monostable(VoxelMonostable::default());
+    assert!(block.prepare().is_err());
+}
+
+#[test]
+fn random_test()
+{
+    use crate::block::{VoxelPowered, VoxelRandom};
+    use crate::scene::{Scene, SceneData};
+
+    let mut scene = Scene::default();
+    let free = scene.place_block(Block::Random(VoxelRandom { seed: 42, ..Default::default() }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered::default()), Coord::new(4, 0, -2), Default::default()).unwrap();
+    let clocked = scene.place_block(Block::Random(VoxelRandom { seed: 42, clocked: true, ..Default::default() }), Coord::new(4, 0, 0), Default::default()).unwrap();
+
+    let bits = |scene: &mut Scene, ticks: usize| -> Vec<(bool, bool)> {
+        (0..ticks)
+            .map(|_| {
+                scene.simulate_tick();
+                (scene.get_block(free).unwrap().2.get_circuit_power(), scene.get_block(clocked).unwrap().2.get_circuit_power())
+            })
+            .collect()
+    };
+
+    // The free-running source produces a mix of bits, while the clocked one holds until its clock rises
+    let first = bits(&mut scene, 16);
+    assert!(first.iter().any(|e| e.0) && first.iter().any(|e| !e.0));
+    assert!(first.iter().all(|e| !e.1));
+
+    scene.update_block(toggle, Block::Toggle(VoxelPowered { powered: true }));
+    scene.simulate_tick();
+    let mut expected = Block::Random(VoxelRandom { seed: 42, ..Default::default() });
+    expected.prepare().unwrap();
+    expected.kind().evaluate(&mut expected, &Default::default(), &Context::default());
+    assert_eq!(scene.get_block(clocked).unwrap().2.get_circuit_power(), expected.get_circuit_power());
+
+    // A saved world continues the same sequence
+    let data = serde_json::to_string(&SceneData::from(scene.clone())).unwrap();
+    let mut loaded = Scene::from(serde_json::from_str::<SceneData>(&data).unwrap());
+    assert_eq!(bits(&mut loaded, 16), bits(&mut scene, 16));
+}
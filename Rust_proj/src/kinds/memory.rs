@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::block::{Block, bus_mask, circuit_voxel, ClockEdge, MAX_BUS_WIDTH, MAX_MEMORY_DEPTH, PowerState, Signal, VoxelFlipFlop, VoxelID};
+use crate::grid::Coord;
+use crate::kinds::gate::{gate_faces, gate_structure, gate_structure_with, unary_gate_structure};
+use crate::kinds::resource_path;
+use crate::registry::{BlockKind, Context, input_power, input_signal};
+
+/// Directory ROM files are loaded from
+pub const ROM_DIRECTORY: &str = "./roms/";
+
+/// Latch whose stored value flips whenever its input turns on
+pub struct ToggleLatch;
+
+impl BlockKind for ToggleLatch
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        unary_gate_structure()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::ToggleLatch(data) => [(circuit_voxel("out"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::ToggleLatch(data) = block else { return };
+
+        let input_state = input_power(inputs, "in");
+
+        data.stored = if input_state && !data.powered {
+            !data.stored
+        } else {
+            data.stored
+        };
+        data.powered = input_state;
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Latch which stays powered for a number of ticks after each tick its input is powered
+pub struct PulseLatch;
+
+impl BlockKind for PulseLatch
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        unary_gate_structure()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::PulseLatch(data) => [(circuit_voxel("out"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::PulseLatch(data) = block else { return };
+
+        data.pulse_battery = data.pulse_battery.saturating_sub(1) +
+            if input_power(inputs, "in") {
+                data.pulse_ticks
+            } else {
+                0
+            };
+        data.powered = data.pulse_battery > 0;
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Latch which is set by `in_a` and reset by `in_b` (keeping its value when both are equal)
+pub struct MemoryLatch;
+
+impl BlockKind for MemoryLatch
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        gate_structure(&[circuit_voxel("in_a"), circuit_voxel("in_b")])
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::MemoryLatch(data) => [(circuit_voxel("out"), Signal::from(data.powered))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::MemoryLatch(data) = block else { return };
+
+        let (input_a_state, input_b_state) = (input_power(inputs, "in_a"), input_power(inputs, "in_b"));
+
+        if input_a_state != input_b_state {
+            data.stored = input_a_state;
+        }
+        data.powered = data.stored;
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Get the state of a flip-flop
+fn get_flip_flop_data(block: &Block) -> Option<&VoxelFlipFlop> {
+    match block {
+        Block::DFlipFlop(data) | Block::TFlipFlop(data) | Block::JKFlipFlop(data) | Block::SRFlipFlop(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Get the mutable state of a flip-flop
+fn get_flip_flop_data_mut(block: &mut Block) -> Option<&mut VoxelFlipFlop> {
+    match block {
+        Block::DFlipFlop(data) | Block::TFlipFlop(data) | Block::JKFlipFlop(data) | Block::SRFlipFlop(data) => Some(data),
+        _ => None
+    }
+}
+
+/// Flip-flop which updates its stored value on an edge of `clk` and outputs it on `q` (and its inverse on `q_bar`)
+///
+/// The stored value is cleared whenever `reset` is powered, regardless of the clock
+pub struct FlipFlop
+{
+    /// Names of the data inputs
+    pub inputs: &'static [&'static str],
+    /// Compute the next stored value from the data inputs and the current stored value
+    pub next: fn(&[PowerState], PowerState) -> PowerState,
+}
+
+impl BlockKind for FlipFlop
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        let inputs = self.inputs
+            .iter()
+            .chain(&["clk", "reset"])
+            .map(|e| circuit_voxel(e))
+            .collect::<Vec<_>>();
+
+        gate_structure_with(&inputs, &[circuit_voxel("q"), circuit_voxel("q_bar")])
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        get_flip_flop_data(block)
+            .map(|data| [
+                (circuit_voxel("q"), Signal::from(data.stored)),
+                (circuit_voxel("q_bar"), Signal::from(!data.stored)),
+            ].into())
+            .unwrap_or_default()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Some(data) = get_flip_flop_data_mut(block) else { return };
+
+        // Edges are detected against the clock input seen during the last tick
+        let clock = input_power(inputs, "clk");
+        let edge = match data.edge {
+            ClockEdge::Rising => clock && !data.clock,
+            ClockEdge::Falling => !clock && data.clock,
+        };
+        data.clock = clock;
+
+        if input_power(inputs, "reset") {
+            data.stored = false;
+        } else if edge {
+            let states = self.inputs
+                .iter()
+                .map(|e| input_power(inputs, e))
+                .collect::<Vec<_>>();
+
+            data.stored = (self.next)(&states, data.stored);
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Load the words of a ROM from the given file
+///
+/// Files with a `.hex` extension contain whitespace-separated hexadecimal words,
+/// other files contain little-endian binary words of the smallest whole number of bytes that fits `word_width`
+pub fn load_rom_file(path: &Path, word_width: u8, depth: u32) -> Result<Vec<Signal>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read ROM file {}: {}", path.display(), e))?;
+
+    let words = if path.extension().is_some_and(|e| e == "hex") {
+        String::from_utf8_lossy(&bytes)
+            .split_whitespace()
+            .map(|e| Signal::from_str_radix(e.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("invalid word \"{}\" in ROM file {}", e, path.display())))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        let word_bytes = (word_width.clamp(1, MAX_BUS_WIDTH) as usize).div_ceil(8);
+
+        bytes
+            .chunks(word_bytes)
+            .map(|e| e.iter().rev().fold(0, |word, byte| word << 8 | Signal::from(*byte)))
+            .collect()
+    };
+
+    if words.len() > memory_depth(depth) as usize {
+        return Err(format!("ROM file {} has {} words but the ROM only has {}", path.display(), words.len(), memory_depth(depth)));
+    }
+
+    Ok(words.into_iter().map(|e| e & bus_mask(word_width)).collect())
+}
+
+/// Get the number of words of a ROM or RAM block
+fn memory_depth(depth: u32) -> u32 {
+    depth.clamp(1, MAX_MEMORY_DEPTH)
+}
+
+/// Read the word at the given address (0 if out of range)
+fn read_word(contents: &[Signal], depth: u32, address: Signal) -> Signal {
+    if address < Signal::from(memory_depth(depth)) {
+        contents.get(address as usize).copied().unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Read-only memory outputting the word at `addr` on `out`
+pub struct Rom;
+
+impl BlockKind for Rom
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        gate_structure(&[circuit_voxel("addr")])
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Rom(data) => [(circuit_voxel("out"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Rom(data) = block else { return };
+
+        data.value = read_word(&data.contents, data.depth, input_signal(inputs, "addr")) & bus_mask(data.word_width);
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        let Block::Rom(data) = block else { return Ok(()) };
+
+        if !data.file.is_empty() {
+            data.contents = load_rom_file(&resource_path(ROM_DIRECTORY, &data.file)?, data.word_width, data.depth)?;
+        }
+        data.contents.truncate(memory_depth(data.depth) as usize);
+
+        Ok(())
+    }
+}
+
+/// Random-access memory outputting the word at `addr` on `out`,
+/// which stores `data` at `addr` when `clk` turns on while `we` is powered
+pub struct Ram;
+
+impl BlockKind for Ram
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        gate_structure(&[circuit_voxel("addr"), circuit_voxel("data"), circuit_voxel("we"), circuit_voxel("clk")])
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Ram(data) => [(circuit_voxel("out"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Ram(data) = block else { return };
+
+        let address = input_signal(inputs, "addr");
+        let clock = input_power(inputs, "clk");
+
+        // Write on the rising edge of the clock
+        if clock && !data.clock && input_power(inputs, "we") && address < Signal::from(memory_depth(data.depth)) {
+            let address = address as usize;
+            if data.contents.len() <= address {
+                data.contents.resize(address + 1, 0);
+            }
+            data.contents[address] = input_signal(inputs, "data") & bus_mask(data.word_width);
+        }
+        data.clock = clock;
+
+        data.value = read_word(&data.contents, data.depth, address) & bus_mask(data.word_width);
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+
+    fn bulk_data(&self, _block: &Block) -> bool {
+        // Writes would otherwise send the whole memory to clients
+        true
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        if let Block::Ram(data) = block {
+            data.contents.truncate(memory_depth(data.depth) as usize);
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn rom_ram_test()
+{
+    use crate::block::{VoxelPowered, VoxelRam, VoxelRom};
+    use crate::scene::Scene;
+
+    let evaluate = |block: &mut Block, inputs: &[(&str, Signal)]| {
+        let inputs = inputs.iter().map(|(name, e)| (circuit_voxel(name), *e)).collect();
+        block.kind().evaluate(block, &inputs, &Context::default());
+    };
+
+    // RAM only stores on the rising edge of the clock while write is enabled
+    let mut ram = Block::Ram(VoxelRam { depth: 16, word_width: 8, ..Default::default() });
+    evaluate(&mut ram, &[("addr", 5), ("data", 0x1ab), ("we", 1), ("clk", 1)]);
+    evaluate(&mut ram, &[("addr", 5), ("data", 0x22), ("we", 1), ("clk", 1)]);
+    evaluate(&mut ram, &[("addr", 6), ("data", 0x33), ("we", 0), ("clk", 0)]);
+    evaluate(&mut ram, &[("addr", 6), ("data", 0x33), ("we", 0), ("clk", 1)]);
+    evaluate(&mut ram, &[("addr", 5)]);
+    assert_eq!(ram, Block::Ram(VoxelRam { depth: 16, word_width: 8, contents: vec![0, 0, 0, 0, 0, 0xab], clock: false, value: 0xab }));
+
+    // RAM contents persist through the protocol encoding
+    assert_eq!(serde_json::from_str::<Block>(&serde_json::to_string(&ram).unwrap()).unwrap(), ram);
+
+    // Writes to a large RAM are only reported when its output changes
+    let mut scene = Scene::default();
+    let ram = scene.add_block(Block::Ram(VoxelRam { depth: MAX_MEMORY_DEPTH, word_width: 8, ..Default::default() }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let toggles = [-3, -1, 1, 3].map(|x| scene.add_block(Block::Toggle(VoxelPowered { powered: x == 1 }), Coord::new(x, 0, -2), Default::default()).unwrap());
+    let [addr, data, _, clk] = toggles;
+    scene.update_block(addr, Block::Toggle(VoxelPowered { powered: true }));
+    scene.update_block(clk, Block::Toggle(VoxelPowered { powered: true }));
+    assert!(!scene.simulate_tick().contains(&ram));
+    assert!(matches!(scene.get_block(ram).unwrap().2, Block::Ram(data) if data.contents == [0, 0]));
+
+    scene.update_block(clk, Block::Toggle(VoxelPowered { powered: false }));
+    scene.simulate_tick();
+    scene.update_block(data, Block::Toggle(VoxelPowered { powered: true }));
+    scene.update_block(clk, Block::Toggle(VoxelPowered { powered: true }));
+    assert!(scene.simulate_tick().contains(&ram));
+    assert_eq!(scene.get_block(ram).unwrap().2.get_circuit_voxel_signal()[&circuit_voxel("out")], Some(1));
+
+    // ROM files are either hexadecimal text or little-endian binary
+    let directory = std::env::temp_dir();
+    fs::write(directory.join("rom_ram_test.hex"), "0x12 34\nfff").unwrap();
+    fs::write(directory.join("rom_ram_test.bin"), [0x34, 0x12, 0x78, 0x56]).unwrap();
+    assert_eq!(load_rom_file(&directory.join("rom_ram_test.hex"), 8, 4), Ok(vec![0x12, 0x34, 0xff]));
+    assert_eq!(load_rom_file(&directory.join("rom_ram_test.bin"), 16, 4), Ok(vec![0x1234, 0x5678]));
+    assert!(load_rom_file(&directory.join("rom_ram_test.hex"), 8, 2).is_err());
+
+    let mut rom = Block::Rom(VoxelRom { depth: 4, word_width: 16, contents: vec![0x1234, 0x5678], ..Default::default() });
+    evaluate(&mut rom, &[("addr", 1)]);
+    assert_eq!(rom.get_circuit_voxel_signal()[&circuit_voxel("out")], Some(0x5678));
+    evaluate(&mut rom, &[("addr", 4)]);
+    assert_eq!(rom.get_circuit_voxel_signal()[&circuit_voxel("out")], Some(0));
+
+    // ROM files cannot be loaded from outside of the ROM directory
+    for file in ["../world.json", "/etc/hosts"] {
+        let mut rom = Block::Rom(VoxelRom { depth: 4, word_width: 8, file: file.to_string(), ..Default::default() });
+        assert!(rom.prepare().is_err());
+    }
+}
+
+#[test]
+fn flip_flop_test()
+{
+    use crate::block::VoxelFlipFlop;
+
+    let evaluate = |block: &mut Block, inputs: &[(&str, Signal)]| {
+        let inputs = inputs.iter().map(|(name, e)| (circuit_voxel(name), *e)).collect();
+        block.kind().evaluate(block, &inputs, &Context::default());
+        (block.get_circuit_voxel_signal()[&circuit_voxel("q")], block.get_circuit_voxel_signal()[&circuit_voxel("q_bar")])
+    };
+
+    // D flip-flops only sample their input on the configured edge
+    let mut d = Block::DFlipFlop(VoxelFlipFlop::default());
+    assert_eq!(evaluate(&mut d, &[("d", 1)]), (Some(0), Some(1)));
+    assert_eq!(evaluate(&mut d, &[("d", 1), ("clk", 1)]), (Some(1), Some(0)));
+    assert_eq!(evaluate(&mut d, &[("d", 0), ("clk", 1)]), (Some(1), Some(0)));
+    assert_eq!(evaluate(&mut d, &[("d", 0)]), (Some(1), Some(0)));
+    assert_eq!(evaluate(&mut d, &[("d", 1), ("reset", 1)]), (Some(0), Some(1)));
+
+    let mut d = Block::DFlipFlop(VoxelFlipFlop { edge: ClockEdge::Falling, ..Default::default() });
+    assert_eq!(evaluate(&mut d, &[("d", 1), ("clk", 1)]), (Some(0), Some(1)));
+    assert_eq!(evaluate(&mut d, &[("d", 1)]), (Some(1), Some(0)));
+
+    // T, JK and SR flip-flops on successive rising edges
+    for (mut block, steps) in [
+        (Block::TFlipFlop(VoxelFlipFlop::default()), vec![(vec![("t", 1)], 1), (vec![("t", 0)], 1), (vec![("t", 1)], 0)]),
+        (Block::JKFlipFlop(VoxelFlipFlop::default()), vec![(vec![("j", 1)], 1), (vec![("j", 1), ("k", 1)], 0), (vec![("j", 1), ("k", 1)], 1), (vec![("k", 1)], 0), (vec![], 0)]),
+        (Block::SRFlipFlop(VoxelFlipFlop::default()), vec![(vec![("s", 1)], 1), (vec![], 1), (vec![("r", 1)], 0), (vec![("s", 1), ("r", 1)], 1)]),
+    ] {
+        for (inputs, q) in steps {
+            evaluate(&mut block, &inputs);
+            let clocked = inputs.into_iter().chain([("clk", 1)]).collect::<Vec<_>>();
+            assert_eq!(evaluate(&mut block, &clocked).0, Some(q), "{}", block);
+        }
+    }
+}
+
+#[test]
+fn mirrored_latch_test()
+{
+    use crate::block::{Axis, Orient, VoxelMemory, VoxelPowered};
+    use crate::scene::Scene;
+
+    // Mirroring a memory latch across X swaps its set and reset inputs
+    let latch = Block::MemoryLatch(VoxelMemory::default());
+    let mirrored = latch.get_global_structure(Coord::new(0, 0, 0), Orient::FORWARD.with_mirror(Some(Axis::X)));
+    assert_eq!(mirrored[&circuit_voxel("in_a")], latch.get_structure()[&circuit_voxel("in_b")]);
+
+    for (orientation, stored) in [(Orient::FORWARD, true), (Orient::FORWARD.with_mirror(Some(Axis::X)), false)] {
+        let mut scene = Scene::default();
+        let latch = scene.add_block(latch.clone(), Coord::new(1, 0, 1), orientation).unwrap();
+        scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+
+        scene.simulate_tick();
+        assert_eq!(scene.get_block(latch).unwrap().2, Block::MemoryLatch(VoxelMemory { stored, powered: stored }));
+
+        // Pasting a mirrored copy of the whole circuit keeps its behavior
+        let region = scene.get_region(Coord::new(-5, -5, -5), Coord::new(5, 5, 5));
+        let mut pasted = Scene::default();
+        let ids = pasted.paste_region(&region, Coord::new(10, 0, 0), Orient::RIGHT.with_mirror(Some(Axis::X))).unwrap();
+        assert!(pasted.paste_region(&region, Coord::new(10, 0, 0), Orient::RIGHT.with_mirror(Some(Axis::X))).is_none());
+        assert_eq!(pasted.get_blocks().len(), 2);
+
+        pasted.simulate_tick();
+        let latch = ids.into_iter().find(|e| matches!(pasted.get_block(*e).unwrap().2, Block::MemoryLatch(_))).unwrap();
+        assert_eq!(pasted.get_block(latch).unwrap().2, Block::MemoryLatch(VoxelMemory { stored, powered: stored }));
+    }
+}
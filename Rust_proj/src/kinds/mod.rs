@@ -0,0 +1,208 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::registry::BlockRegistry;
+
+pub mod gate;
+pub mod io;
+pub mod memory;
+pub mod module;
+pub mod wire;
+
+/// Resolve a file referenced by block data, which must be a relative path inside the given directory
+pub fn resource_path(directory: &str, file: &str) -> Result<PathBuf, String> {
+    let path = Path::new(file);
+
+    if file.is_empty() || !path.components().all(|e| matches!(e, Component::Normal(_))) {
+        return Err(format!("\"{}\" is not a relative path inside {}", file, directory));
+    }
+
+    Ok(Path::new(directory).join(path))
+}
+
+/// Register all of the built-in block kinds
+pub fn register_builtin(registry: &mut BlockRegistry) {
+    registry.register("wire", Arc::new(wire::Wire));
+    registry.register("bus", Arc::new(wire::Bus));
+    registry.register("bus_splitter", Arc::new(wire::BusSplitter));
+    registry.register("bus_merger", Arc::new(wire::BusMerger));
+    registry.register("crossing", Arc::new(wire::Crossing));
+
+    registry.register("air", Arc::new(io::Air));
+    registry.register("block", Arc::new(io::Solid));
+    registry.register("toggle", Arc::new(io::Toggle));
+    registry.register("button", Arc::new(io::Button));
+    registry.register("keypad", Arc::new(io::Keypad));
+    registry.register("dial", Arc::new(io::Dial));
+    registry.register("pixel", Arc::new(io::Pixel));
+    registry.register("color_pixel", Arc::new(io::ColorPixel));
+    registry.register("display", Arc::new(io::Display));
+    registry.register("seven_segment", Arc::new(io::SevenSegment));
+    registry.register("hex_digit", Arc::new(io::HexDigit));
+    registry.register("clock", Arc::new(io::Clock));
+    registry.register("pulse", Arc::new(io::Pulse));
+    registry.register("monostable", Arc::new(io::Monostable));
+    registry.register("random", Arc::new(io::Random));
+    registry.register("transmitter", Arc::new(io::Transmitter));
+    registry.register("receiver", Arc::new(io::Receiver));
+
+    registry.register("and_gate", Arc::new(gate::Gate(|e| e.iter().all(|e| *e))));
+    registry.register("or_gate", Arc::new(gate::Gate(|e| e.iter().any(|e| *e))));
+    registry.register("xor_gate", Arc::new(gate::Gate(|e| e.iter().filter(|e| **e).count() % 2 == 1)));
+    registry.register("nand_gate", Arc::new(gate::Gate(|e| !e.iter().all(|e| *e))));
+    registry.register("nor_gate", Arc::new(gate::Gate(|e| !e.iter().any(|e| *e))));
+    registry.register("xnor_gate", Arc::new(gate::Gate(|e| e.iter().filter(|e| **e).count() % 2 == 0)));
+    registry.register("not_gate", Arc::new(gate::UnaryGate(|a| !a)));
+    registry.register("diode", Arc::new(gate::UnaryGate(|a| a)));
+    registry.register("lut", Arc::new(gate::Lut));
+    registry.register("repeater", Arc::new(gate::Repeater));
+    registry.register("comparator", Arc::new(gate::Comparator));
+    registry.register("amplifier", Arc::new(gate::Amplifier));
+    registry.register("mux", Arc::new(gate::Selector(gate::SelectorMode::Mux)));
+    registry.register("demux", Arc::new(gate::Selector(gate::SelectorMode::Demux)));
+    registry.register("encoder", Arc::new(gate::Selector(gate::SelectorMode::Encoder)));
+    registry.register("decoder", Arc::new(gate::Selector(gate::SelectorMode::Decoder)));
+
+    registry.register("toggle_latch", Arc::new(memory::ToggleLatch));
+    registry.register("pulse_latch", Arc::new(memory::PulseLatch));
+    registry.register("memory_latch", Arc::new(memory::MemoryLatch));
+    registry.register("d_flip_flop", Arc::new(memory::FlipFlop {
+        inputs: &["d"],
+        next: |e, _| e[0],
+    }));
+    registry.register("t_flip_flop", Arc::new(memory::FlipFlop {
+        inputs: &["t"],
+        next: |e, q| q ^ e[0],
+    }));
+    registry.register("jk_flip_flop", Arc::new(memory::FlipFlop {
+        inputs: &["j", "k"],
+        next: |e, q| e[0] && !q || !e[1] && q,
+    }));
+    // Set takes priority when both inputs are powered
+    registry.register("sr_flip_flop", Arc::new(memory::FlipFlop {
+        inputs: &["s", "r"],
+        next: |e, q| e[0] || !e[1] && q,
+    }));
+    registry.register("rom", Arc::new(memory::Rom));
+    registry.register("ram", Arc::new(memory::Ram));
+
+    registry.register("port", Arc::new(module::Port));
+    registry.register("module", Arc::new(module::Module));
+}
+
+/// Get a block of each built-in kind
+#[cfg(test)]
+fn builtin_samples() -> Vec<crate::block::Block> {
+    use crate::block::Block;
+
+    vec![
+        Block::Air,
+        Block::Wire(Default::default()),
+        Block::Bus(Default::default()),
+        Block::BusSplitter(Default::default()),
+        Block::BusMerger(Default::default()),
+        Block::Crossing(Default::default()),
+        Block::Block(Default::default()),
+        Block::Toggle(Default::default()),
+        Block::Button(Default::default()),
+        Block::Keypad(Default::default()),
+        Block::Dial(Default::default()),
+        Block::Pixel(Default::default()),
+        Block::ColorPixel(Default::default()),
+        Block::Display(Default::default()),
+        Block::SevenSegment(Default::default()),
+        Block::HexDigit(Default::default()),
+        Block::ANDGate(Default::default()),
+        Block::ORGate(Default::default()),
+        Block::XORGate(Default::default()),
+        Block::NANDGate(Default::default()),
+        Block::NORGate(Default::default()),
+        Block::XNORGate(Default::default()),
+        Block::NOTGate(Default::default()),
+        Block::Diode(Default::default()),
+        Block::Lut(Default::default()),
+        Block::Repeater(Default::default()),
+        Block::Comparator(Default::default()),
+        Block::Amplifier(Default::default()),
+        Block::Mux(Default::default()),
+        Block::Demux(Default::default()),
+        Block::Encoder(Default::default()),
+        Block::Decoder(Default::default()),
+        Block::Clock(Default::default()),
+        Block::Pulse(Default::default()),
+        Block::Monostable(Default::default()),
+        Block::Random(Default::default()),
+        Block::Transmitter(Default::default()),
+        Block::Receiver(Default::default()),
+        Block::ToggleLatch(Default::default()),
+        Block::PulseLatch(Default::default()),
+        Block::MemoryLatch(Default::default()),
+        Block::DFlipFlop(Default::default()),
+        Block::TFlipFlop(Default::default()),
+        Block::JKFlipFlop(Default::default()),
+        Block::SRFlipFlop(Default::default()),
+        Block::Rom(Default::default()),
+        Block::Ram(Default::default()),
+        Block::Port(Default::default()),
+        Block::Module(Default::default()),
+    ]
+
+}
+
+#[test]
+fn builtin_names_test()
+{
+    use crate::registry::registry;
+
+    // Every built-in block must be registered under its protocol name
+    for block in builtin_samples() {
+        let name = serde_json::to_value(&block).unwrap()["block"].as_str().unwrap().to_string();
+        assert_eq!(block.kind_name(), name);
+        assert!(registry().read().unwrap().get(&name).is_some(), "block kind \"{}\" is not registered", name);
+    }
+}
+
+#[test]
+fn builtin_rotation_test()
+{
+    use crate::block::{Axis, Block, Orient, VoxelClock, VoxelGate, VoxelLut, VoxelSelector};
+    use crate::grid::Coord;
+
+    let blocks = builtin_samples()
+        .into_iter()
+        .chain([
+            Block::ANDGate(VoxelGate { inputs: 5, powered: false }),
+            Block::Lut(VoxelLut { inputs: 3, ..Default::default() }),
+            Block::Mux(VoxelSelector { select_bits: 3, ..Default::default() }),
+            Block::Encoder(VoxelSelector { select_bits: 2, ..Default::default() }),
+            Block::Clock(VoxelClock { enable: true, ..Default::default() }),
+        ]);
+
+    // Every orientation (mirrored or not) of every block must be a rigid transform of its structure
+    for block in blocks {
+        let structure = block.get_structure();
+        let position = Coord::new(3, -7, 11);
+
+        for orientation in Orient::all().flat_map(|e| [None, Some(Axis::X), Some(Axis::Y), Some(Axis::Z)].map(|m| e.with_mirror(m))) {
+            let global = block.get_global_structure(position, orientation);
+            assert_eq!(global.len(), structure.len());
+
+            // Declared terminal faces lead out of the block
+            for (voxel_id, faces) in block.get_global_terminal_faces(orientation) {
+                assert!(faces.iter().all(|e| !global.values().any(|v| *v == global[&voxel_id] + e)),
+                        "{} of {} faces into its own block", voxel_id, block);
+            }
+
+            for (a, local_a) in &structure {
+                assert_eq!(global[a] - position, orientation.rotate(*local_a));
+
+                for (b, local_b) in &structure {
+                    let (local, rotated) = (local_a - local_b, global[a] - global[b]);
+                    assert_eq!(local.x * local.x + local.y * local.y + local.z * local.z,
+                               rotated.x * rotated.x + rotated.y * rotated.y + rotated.z * rotated.z,
+                               "{} is not rigid when oriented {:?}", block, orientation);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use cgmath::Zero;
+
+use crate::block::{Block, circuit_voxel, PortDirection, Signal, VoxelID};
+use crate::grid::Coord;
+use crate::kinds::resource_path;
+use crate::registry::{BlockKind, Context, input_signal};
+use crate::scene::Scene;
+
+/// Directory module scenes are loaded from (and saved to)
+pub const MODULE_DIRECTORY: &str = "./modules/";
+
+/// Load the scene saved in the given module file (relative to `MODULE_DIRECTORY`)
+pub fn load_module_file(file: &str) -> Result<Scene, String> {
+    let path = resource_path(MODULE_DIRECTORY, file)?;
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read module file {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("failed to load module file {}: {}", path.display(), e))
+}
+
+/// Terminal of a module, driving its value into the module's scene (input) or reading it from the scene (output)
+pub struct Port;
+
+impl BlockKind for Port
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("port"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Port(data) if data.direction == PortDirection::Input => [(circuit_voxel("port"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        // Input ports are driven by the module containing them
+        if let Block::Port(data) = block {
+            if data.direction == PortDirection::Output {
+                data.value = input_signal(inputs, "port");
+            }
+        }
+    }
+}
+
+/// Block simulating a nested scene, advanced by one tick every tick
+///
+/// The module has the shape of its scene, with a terminal in place of each of its ports
+pub struct Module;
+
+impl BlockKind for Module
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        let Block::Module(data) = block else { return Default::default() };
+
+        data.scene
+            .get_blocks()
+            .values()
+            .flat_map(|(location, orientation, block)| {
+                let structure = block.get_global_structure(*location, *orientation);
+
+                match block {
+                    Block::Port(port) => structure
+                        .into_values()
+                        .map(|e| (circuit_voxel(&port.name), e))
+                        .collect::<Vec<_>>(),
+                    _ => structure
+                        .into_values()
+                        .map(|e| (format!("solid-{}-{}-{}", e.x, e.y, e.z), e))
+                        .collect::<Vec<_>>()
+                }
+            })
+            .collect()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        let Block::Module(data) = block else { return Default::default() };
+
+        data.scene
+            .get_blocks()
+            .values()
+            .filter_map(|(_, _, e)| match e {
+                Block::Port(port) if port.direction == PortDirection::Output => Some((circuit_voxel(&port.name), port.value)),
+                _ => None
+            })
+            .collect()
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        let Block::Module(data) = block else { return };
+
+        // Drive the input ports from the module's terminals, then advance the scene
+        let input_ports = data.scene
+            .get_blocks()
+            .iter()
+            .filter_map(|(id, (_, _, e))| match e {
+                Block::Port(port) if port.direction == PortDirection::Input => Some((*id, port.clone())),
+                _ => None
+            })
+            .collect::<Vec<_>>();
+
+        for (id, mut port) in input_ports {
+            port.value = input_signal(inputs, &port.name);
+            data.scene.update_block(id, Block::Port(port));
+        }
+
+        data.scene.simulate_tick();
+    }
+
+    fn bulk_data(&self, _block: &Block) -> bool {
+        // Any activity inside the module would otherwise send its whole scene to clients
+        true
+    }
+
+    fn prepare(&self, block: &mut Block) -> Result<(), String> {
+        let Block::Module(data) = block else { return Ok(()) };
+
+        if !data.file.is_empty() {
+            *data.scene = load_module_file(&data.file)?;
+        }
+
+        // Every port must have a distinct name
+        let mut names = HashSet::new();
+        for (_, _, e) in data.scene.get_blocks().values() {
+            if let Block::Port(port) = e {
+                if port.name.is_empty() || !names.insert(port.name.clone()) {
+                    return Err(format!("module has an unnamed or duplicate port \"{}\"", port.name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn module_test()
+{
+    use crate::block::{VoxelModule, VoxelPort, VoxelPowered, VoxelWire};
+    use crate::scene::SceneData;
+
+    // Inverter made of a NOT gate between two ports, surrounded by blocks outside of the region saved as the module
+    let mut world = Scene::default();
+    world.add_block(Block::Port(VoxelPort { name: "a".to_string(), direction: PortDirection::Input, value: 0 }), Coord::new(5, 0, 0), Default::default()).unwrap();
+    world.add_block(Block::NOTGate(Default::default()), Coord::new(5, 0, 1), Default::default()).unwrap();
+    world.add_block(Block::Port(VoxelPort { name: "y".to_string(), direction: PortDirection::Output, value: 0 }), Coord::new(5, 0, 3), Default::default()).unwrap();
+    world.add_block(Block::Block(Default::default()), Coord::new(6, 0, 0), Default::default()).unwrap();
+    world.add_block(Block::Block(Default::default()), Coord::new(5, 0, 4), Default::default()).unwrap();
+
+    let inverter = world.get_region(Coord::new(5, 0, 0), Coord::new(5, 0, 3));
+    assert_eq!(inverter.get_blocks().len(), 3);
+
+    let mut module = Block::Module(VoxelModule { file: "".to_string(), scene: Box::new(inverter) });
+    module.prepare().unwrap();
+    assert_eq!(module.get_structure(), [
+        (circuit_voxel("a"), Coord::new(0, 0, 0)),
+        (circuit_voxel("y"), Coord::new(0, 0, 3)),
+        ("solid-0-0-1".to_string(), Coord::new(0, 0, 1)),
+        ("solid-0-0-2".to_string(), Coord::new(0, 0, 2)),
+    ].into());
+
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    let module = scene.add_block(module, Coord::new(0, 0, 0), Default::default()).unwrap();
+    let wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(0, 0, 4), Default::default()).unwrap();
+
+    for _ in 0..3 {
+        scene.simulate_tick();
+    }
+    assert_eq!(scene.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: false, ..Default::default() }));
+
+    // The module's internal state survives a save/load round trip
+    let data = serde_json::to_string(&SceneData::from(scene.clone())).unwrap();
+    let mut scene = Scene::from(serde_json::from_str::<SceneData>(&data).unwrap());
+
+    // The module is only reported as changed when its output does, though its scene changes on every tick
+    scene.update_block(toggle, Block::Toggle(VoxelPowered { powered: false }));
+    let mut states = vec![scene.get_block(module).unwrap().2];
+    let mut reported = vec![];
+    for _ in 0..3 {
+        reported.push(scene.simulate_tick().contains(&module));
+        states.push(scene.get_block(module).unwrap().2);
+    }
+    assert_eq!(scene.get_block(wire).unwrap().2, Block::Wire(VoxelWire { powered: true, ..Default::default() }));
+    assert_eq!(reported.iter().filter(|e| **e).count(), 1);
+    assert!(states.windows(2).filter(|e| e[0] != e[1]).count() > 1);
+
+    // Ports must be distinguishable and module files must stay inside the module directory
+    let mut duplicate = Scene::default();
+    for x in [0, 2] {
+        duplicate.add_block(Block::Port(VoxelPort { name: "a".to_string(), ..Default::default() }), Coord::new(x, 0, 0), Default::default()).unwrap();
+    }
+    assert!(Block::Module(VoxelModule { file: "".to_string(), scene: Box::new(duplicate) }).prepare().is_err());
+    assert!(Block::Module(VoxelModule { file: "../world.json".to_string(), ..Default::default() }).prepare().is_err());
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use cgmath::Zero;
+
+use crate::block::{bit_voxel, Block, bus_mask, circuit_voxel, MAX_BUS_WIDTH, MAX_STRENGTH, Signal, VoxelID};
+use crate::grid::Coord;
+use crate::kinds::gate::gate_faces;
+use crate::registry::{BlockKind, Context, input_power, input_signal};
+
+/// Return the voxels that make up a bus splitter/merger of the given width
+///
+/// The bus terminal sits at `(0, 0, bus_z)` and the bit terminals are spaced out along +X at `bits_z`
+/// so that wires leaving adjacent bits do not touch
+fn bus_adapter_structure(width: u8, bus_z: i32, bits_z: i32) -> HashMap<VoxelID, Coord> {
+    let width = width.clamp(1, MAX_BUS_WIDTH) as i32;
+
+    (0..2 * width - 1)
+        .flat_map(|x| [(x, bus_z), (x, bits_z)])
+        .map(|(x, z)| {
+            let id = if x == 0 && z == bus_z {
+                circuit_voxel("bus")
+            } else if x % 2 == 0 && z == bits_z {
+                bit_voxel((x / 2) as u8)
+            } else {
+                format!("solid-{}-{}", x, z)
+            };
+            (id, Coord::new(x, 0, z))
+        })
+        .collect()
+}
+
+/// Single-bit conductor
+pub struct Wire;
+
+impl BlockKind for Wire
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("wire"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Wire(data) => [(circuit_voxel("wire"), Signal::from(data.strength).max(Signal::from(data.powered)))].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, context: &Context) {
+        if let Block::Wire(data) = block {
+            let signal = input_signal(inputs, "wire");
+            data.powered = signal != 0;
+            data.strength = if context.analog { signal.min(MAX_STRENGTH) as u8 } else { 0 };
+        }
+    }
+
+    fn conductor_width(&self, _block: &Block) -> Option<u8> {
+        Some(1)
+    }
+
+    fn conductor_color(&self, block: &Block) -> Option<u8> {
+        match block {
+            Block::Wire(data) => Some(data.color),
+            _ => Some(0)
+        }
+    }
+}
+
+/// Multi-bit conductor (1-64 bits)
+pub struct Bus;
+
+impl BlockKind for Bus
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("bus"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Bus(data) => [(circuit_voxel("bus"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Block::Bus(data) = block {
+            data.value = input_signal(inputs, "bus") & bus_mask(data.width);
+        }
+    }
+
+    fn conductor_width(&self, block: &Block) -> Option<u8> {
+        match block {
+            Block::Bus(data) => Some(data.width.clamp(1, MAX_BUS_WIDTH)),
+            _ => None
+        }
+    }
+}
+
+/// Single-bit conductor with two independent channels sharing its voxel,
+/// `x` connecting along the block's X axis and `z` along its Z axis, so that wires can cross without joining
+pub struct Crossing;
+
+impl BlockKind for Crossing
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [(circuit_voxel("x"), Coord::zero()), (circuit_voxel("z"), Coord::zero())].into()
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::Crossing(data) => [
+                (circuit_voxel("x"), Signal::from(data.x)),
+                (circuit_voxel("z"), Signal::from(data.z)),
+            ].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Block::Crossing(data) = block {
+            data.x = input_power(inputs, "x");
+            data.z = input_power(inputs, "z");
+        }
+    }
+
+    fn conductor_width(&self, _block: &Block) -> Option<u8> {
+        Some(1)
+    }
+
+    fn conductor_color(&self, _block: &Block) -> Option<u8> {
+        // Crossings carry wires of any color
+        None
+    }
+
+    fn terminal_faces(&self, _block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        [
+            (circuit_voxel("x"), vec![Coord::new(-1, 0, 0), Coord::new(1, 0, 0)]),
+            (circuit_voxel("z"), vec![Coord::new(0, 0, -1), Coord::new(0, 0, 1)]),
+        ].into()
+    }
+}
+
+/// Breaks a bus (`bus` input) into single-bit outputs (`bit_0`, `bit_1`, ...)
+pub struct BusSplitter;
+
+impl BlockKind for BusSplitter
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        match block {
+            Block::BusSplitter(data) => bus_adapter_structure(data.width, 0, 1),
+            _ => Default::default()
+        }
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::BusSplitter(data) => (0..data.width.clamp(1, MAX_BUS_WIDTH))
+                .map(|i| (bit_voxel(i), (data.value >> i) & 1))
+                .collect(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        // Each bit terminal outputs its bit of the stored value
+        if let Block::BusSplitter(data) = block {
+            data.value = input_signal(inputs, "bus") & bus_mask(data.width);
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
+
+/// Joins single-bit inputs (`bit_0`, `bit_1`, ...) into a bus (`bus` output)
+pub struct BusMerger;
+
+impl BlockKind for BusMerger
+{
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord> {
+        match block {
+            Block::BusMerger(data) => bus_adapter_structure(data.width, 1, 0),
+            _ => Default::default()
+        }
+    }
+
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal> {
+        match block {
+            Block::BusMerger(data) => [(circuit_voxel("bus"), data.value)].into(),
+            _ => Default::default()
+        }
+    }
+
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, _context: &Context) {
+        if let Block::BusMerger(data) = block {
+            data.value = (0..data.width.clamp(1, MAX_BUS_WIDTH))
+                .filter(|i| inputs.get(&bit_voxel(*i)).is_some_and(|e| *e != 0))
+                .fold(0, |acc, i| acc | (1 << i));
+        }
+    }
+
+    fn terminal_faces(&self, block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        gate_faces(self, block)
+    }
+}
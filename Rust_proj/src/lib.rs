@@ -0,0 +1,9 @@
+pub mod behavior;
+pub mod block;
+pub mod definition;
+pub mod framebuffer;
+pub mod grid;
+pub mod kinds;
+pub mod network;
+pub mod registry;
+pub mod scene;
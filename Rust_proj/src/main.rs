@@ -1,15 +1,11 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
-use log::info;
+use log::{error, info};
 
-use crate::network::Network;
-use crate::scene::Scene;
-
-mod block;
-mod scene;
-mod network;
-mod grid;
+use logic_simulator_server::definition::{DEFINITIONS_PATH, load_definitions};
+use logic_simulator_server::network::Network;
+use logic_simulator_server::scene::Scene;
 
 fn main() {
     // Default server address
@@ -18,6 +14,14 @@ fn main() {
     // Initialize logger with environment variables to control display levels
     simple_logger::init_with_env().unwrap();
 
+    // Load block definitions (if present) before any world data is used
+    if Path::new(DEFINITIONS_PATH).exists() {
+        match load_definitions(Path::new(DEFINITIONS_PATH)) {
+            Ok(names) => info!("loaded block definitions {:?} from \"{}\"", names, DEFINITIONS_PATH),
+            Err(e) => error!("{}", e),
+        }
+    }
+
     // Read arguments (optional port and optional world file to load from)
     let args: Vec<String> = std::env::args().collect();
     let address = args.get(1).unwrap_or(&default_address);
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
 use std::io::{ErrorKind, Read};
 use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::path::PathBuf;
@@ -10,10 +11,14 @@ use cgmath::Vector3;
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 
-use crate::block::{Block, Orient};
+use crate::block::{Block, Orient, Signal, VoxelID};
+use crate::framebuffer::Framebuffer;
 use crate::grid::Coord;
+use crate::kinds::module::{load_module_file, MODULE_DIRECTORY};
+use crate::kinds::resource_path;
 use crate::network::Protocol::BothRequestPlaceBlocks;
-use crate::scene::{InstanceID, Scene};
+use crate::registry::InteractAction;
+use crate::scene::{Channel, InstanceID, Scene};
 
 pub type ClientID = String;
 pub type Message = (ClientID, Protocol);
@@ -81,6 +86,60 @@ pub struct ProtocolUpdateBlock
     rotation: Option<Orient>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     data: Option<Block>,
+    /// Signal of each output terminal, sent by the server instead of the data of blocks holding bulk data
+    /// (see `BlockKind::bulk_data`)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    outputs: Option<HashMap<VoxelID, Signal>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolInteract
+{
+    id: InstanceID,
+    action: InteractAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolSaveModule
+{
+    /// File to save the module to, relative to `MODULE_DIRECTORY`
+    file: String,
+    /// Inclusive bounds of the region to save (the whole world if not given)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    region: Option<(Coord, Coord)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolPasteModule
+{
+    /// File to load the blocks from, relative to `MODULE_DIRECTORY`
+    file: String,
+    position: Coord,
+    /// Orientation of the pasted blocks (which may be mirrored), around the saved region's origin
+    #[serde(default)]
+    rotation: Orient,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolDefineFramebuffer
+{
+    name: String,
+    /// New definition of the framebuffer (removes it if not given)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    framebuffer: Option<Framebuffer>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolCaptureFramebuffer
+{
+    name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolSetAnalog
+{
+    /// Whether wires carry a decaying signal strength rather than on/off signals
+    analog: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -95,6 +154,7 @@ pub struct ProtocolResponseMetadata
 {
     ticks: u32,
     clients: Vec<Client>,
+    analog: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -111,9 +171,17 @@ pub enum Protocol
 
     ClientRequestJoin(Client),
     ClientRequestLeave,
+    ClientRequestInteract(ProtocolInteract),
+    ClientRequestSaveModule(ProtocolSaveModule),
+    ClientRequestPasteModule(ProtocolPasteModule),
+    ClientRequestDefineFramebuffer(ProtocolDefineFramebuffer),
+    ClientRequestCaptureFramebuffer(ProtocolCaptureFramebuffer),
+    ClientRequestSetAnalog(ProtocolSetAnalog),
+    ClientRequestListChannels,
 
     ServerRequestKick,
     ServerResponseMetadata(ProtocolResponseMetadata),
+    ServerResponseChannels(BTreeMap<String, Channel>),
 }
 
 #[ignore]
@@ -146,11 +214,27 @@ pub struct Network
     stream_receiver: MessageReceiver,
 }
 
+/// Capture the framebuffer with the given name to a PPM image in `SERVER_DIRECTORY`
+///
+/// Returns the path of the image
+fn save_framebuffer(world: &Scene, name: &str) -> Result<PathBuf, String> {
+    let frame = world.capture_framebuffer(name)
+        .ok_or_else(|| format!("framebuffer \"{}\" does not exist", name))?;
+    let path = PathBuf::from(format!("{}{}-{}.ppm", SERVER_DIRECTORY, name, world.get_ticks()));
+
+    fs::create_dir_all(SERVER_DIRECTORY)
+        .and_then(|_| fs::write(&path, frame.to_ppm()))
+        .map_err(|e| format!("failed to save framebuffer to {}: {}", path.display(), e))?;
+
+    Ok(path)
+}
+
 impl Network
 {
     /// Handles combined messages from clients (only one instance of this function)
     ///
     /// Communicates with the client handlers via message passing
+    #[allow(clippy::match_result_ok, clippy::needless_return, clippy::redundant_pattern_matching)]
     fn server_handler(queue: MessageReceiver, world: World, clients: Clients, settings: WorldSettings) -> Option<()> {
         let mut w = world.lock().ok()?;
         let mut last_save = Instant::now();
@@ -203,6 +287,18 @@ impl Network
                 let updates = w.simulate_tick();
                 last_tick = Instant::now();
 
+                // Capture framebuffers which are due
+                let due = w.get_framebuffers()
+                    .iter()
+                    .filter(|(_, e)| e.capture_interval > 0 && w.get_ticks().is_multiple_of(e.capture_interval))
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>();
+                for name in due {
+                    if let Err(message) = save_framebuffer(&w, &name) {
+                        error!("{}", message);
+                    }
+                }
+
                 // info!("simulated tick in ~{}ms (versus {}ms maximum)", now.elapsed().as_millis(), settings.tick_duration.as_millis());
 
                 // Send client data to all clients
@@ -214,17 +310,33 @@ impl Network
                         .iter()
                         .filter_map(|(_, (c, _))| c.clone())
                         .collect(),
+                    analog: w.is_analog(),
                 }));
                 for (_, sv_to_cl_sender) in clients.lock().ok()?.iter() {
                     drop(sv_to_cl_sender.1.send(response.clone()));
                 }
 
                 // Send block updates to all clients
-                let deltas = updates.iter().map(|e| ProtocolUpdateBlock {
-                    id: *e,
-                    position: None,
-                    rotation: None,
-                    data: Some(w.get_block(*e).unwrap().2),
+                let deltas = updates.iter().map(|e| {
+                    let block = w.get_block(*e).unwrap().2;
+
+                    if block.kind().bulk_data(&block) {
+                        ProtocolUpdateBlock {
+                            id: *e,
+                            position: None,
+                            rotation: None,
+                            data: None,
+                            outputs: Some(block.kind().outputs(&block)),
+                        }
+                    } else {
+                        ProtocolUpdateBlock {
+                            id: *e,
+                            position: None,
+                            rotation: None,
+                            data: Some(block),
+                            outputs: None,
+                        }
+                    }
                 }).collect();
                 let response = (SERVER_ID.to_string(), Protocol::BothRequestUpdateBlocks(deltas));
                 for (_, sv_to_cl_sender) in clients.lock().ok()?.iter() {
@@ -252,21 +364,24 @@ impl Network
 
                         // Process blocks
                         for i in &data {
-                            if let Some(instanceid) = w.add_block(i.data.clone(), i.position, i.rotation) {
-                                result.push(ProtocolPlaceBlock {
-                                    id: Some(instanceid),
-                                    position: i.position,
-                                    rotation: i.rotation,
-                                    data: i.data.clone(),
-                                });
-                            } else {
-                                // Send error message to client
-                                clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
-                                    ProtocolResponse {
-                                        ok: false,
-                                        message: "block overlaps existing block".to_string(),
-                                    }))).ok()?;
-                                break;
+                            match w.place_block(i.data.clone(), i.position, i.rotation) {
+                                Ok(instanceid) => {
+                                    result.push(ProtocolPlaceBlock {
+                                        id: Some(instanceid),
+                                        position: i.position,
+                                        rotation: i.rotation,
+                                        data: w.get_block(instanceid).unwrap().2,
+                                    });
+                                }
+                                Err(message) => {
+                                    // Send error message to client
+                                    clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                                        ProtocolResponse {
+                                            ok: false,
+                                            message,
+                                        }))).ok()?;
+                                    break;
+                                }
                             }
                         }
 
@@ -293,33 +408,52 @@ impl Network
 
                         // Process blocks
                         for i in &data {
-                            let update_result = if i.position.is_some() || i.rotation.is_some() {
-                                if let Some((coord, orient, block)) = w.get_block(i.id) {
-                                    w.replace_block(i.id,
-                                                    i.data.clone().unwrap_or(block),
-                                                    i.position.unwrap_or(coord),
-                                                    i.rotation.unwrap_or(orient))
-                                } else {
-                                    None
+                            // Prepare the new block data (if any) before applying it
+                            let update_result = i.data
+                                .clone()
+                                .map(|mut d| {
+                                    w.check_rewrite(i.id, &d)?;
+                                    d.prepare().map(|_| d)
+                                })
+                                .transpose()
+                                .and_then(|d| {
+                                    if i.position.is_some() || i.rotation.is_some() {
+                                        let (coord, orient, block) = w.get_block(i.id)
+                                            .ok_or_else(|| "block does not exist".to_string())?;
+
+                                        w.replace_block(i.id,
+                                                        d.clone().unwrap_or(block),
+                                                        i.position.unwrap_or(coord),
+                                                        i.rotation.unwrap_or(orient))
+                                            .ok_or_else(|| "block overlaps existing block".to_string())?;
+                                    } else if let Some(d) = d.clone() {
+                                        w.update_block(i.id, d)
+                                            .ok_or_else(|| "block does not exist".to_string())?;
+                                    } else {
+                                        return Err("no changes given".to_string());
+                                    }
+
+                                    Ok(d)
+                                });
+
+                            match update_result {
+                                Ok(d) => {
+                                    result.push(ProtocolUpdateBlock {
+                                        data: d,
+                                        outputs: None,
+                                        ..i.clone()
+                                    });
+                                }
+                                Err(message) => {
+                                    // Send error message to client
+                                    clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                                        ProtocolResponse {
+                                            ok: false,
+                                            message,
+                                        }))).ok()?;
+                                    break;
                                 }
-                            } else if let Some(d) = i.data.clone() {
-                                w.update_block(i.id, d)
-                                    .map(|_| ())
-                            } else {
-                                None
-                            };
-
-                            if update_result.is_none() {
-                                // Send error message to client
-                                clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
-                                    ProtocolResponse {
-                                        ok: false,
-                                        message: "block overlaps existing block".to_string(),
-                                    }))).ok()?;
-                                break;
                             }
-
-                            result.push(i.clone());
                         }
 
                         // Check for success
@@ -404,6 +538,126 @@ impl Network
                         // Remove client from storage
                         clients.lock().ok()?.remove(&client_id);
                     }
+                    Protocol::ClientRequestInteract(data) => {
+                        let interact_result = w.interact(data.id, &data.action);
+
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                            ProtocolResponse {
+                                ok: interact_result.is_ok(),
+                                message: interact_result.clone().err().unwrap_or_default(),
+                            }))).ok()?;
+
+                        // Send the new block state to all clients
+                        if let Ok(block) = interact_result {
+                            let response = (SERVER_ID.to_string(), Protocol::BothRequestUpdateBlocks(vec![ProtocolUpdateBlock {
+                                id: data.id,
+                                position: None,
+                                rotation: None,
+                                data: Some(block),
+                                outputs: None,
+                            }]));
+                            for (_, sv_to_cl_sender) in clients.lock().ok()?.iter() {
+                                drop(sv_to_cl_sender.1.send(response.clone()));
+                            }
+                        }
+                    }
+                    Protocol::ClientRequestSaveModule(data) => {
+                        let module = match data.region {
+                            Some((min, max)) => w.get_region(min, max),
+                            None => w.clone(),
+                        };
+
+                        let save_result = resource_path(MODULE_DIRECTORY, &data.file)
+                            .and_then(|path| {
+                                fs::create_dir_all(MODULE_DIRECTORY)
+                                    .and_then(|_| fs::write(&path, serde_json::to_string(&module).unwrap()))
+                                    .map_err(|e| format!("failed to save module to {}: {}", path.display(), e))
+                            });
+
+                        if let Err(message) = &save_result {
+                            warn!("client {} failed to save module: {}", client_id, message);
+                        } else {
+                            info!("client {} saved module \"{}\"", client_id, data.file);
+                        }
+
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                            ProtocolResponse {
+                                ok: save_result.is_ok(),
+                                message: save_result.err().unwrap_or_default(),
+                            }))).ok()?;
+                    }
+                    Protocol::ClientRequestPasteModule(data) => {
+                        let paste_result = load_module_file(&data.file)
+                            .and_then(|region| w.paste_region(&region, data.position, data.rotation)
+                                .ok_or_else(|| "block overlaps existing block".to_string()));
+
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                            ProtocolResponse {
+                                ok: paste_result.is_ok(),
+                                message: paste_result.clone().err().unwrap_or_default(),
+                            }))).ok()?;
+
+                        // Send pasted blocks to all clients
+                        if let Ok(ids) = paste_result {
+                            let response = (SERVER_ID.to_string(), BothRequestPlaceBlocks(ids
+                                .into_iter()
+                                .map(|id| {
+                                    let (position, rotation, data) = w.get_block(id).unwrap();
+                                    ProtocolPlaceBlock {
+                                        id: Some(id),
+                                        position,
+                                        rotation,
+                                        data,
+                                    }
+                                })
+                                .collect()));
+                            for (_, sv_to_cl_sender) in clients.lock().ok()?.iter() {
+                                drop(sv_to_cl_sender.1.send(response.clone()));
+                            }
+                        }
+                    }
+                    Protocol::ClientRequestDefineFramebuffer(data) => {
+                        let define_result = match data.framebuffer {
+                            Some(framebuffer) => w.define_framebuffer(&data.name, framebuffer),
+                            None => w.remove_framebuffer(&data.name)
+                                .map(|_| ())
+                                .ok_or_else(|| format!("framebuffer \"{}\" does not exist", data.name)),
+                        };
+
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                            ProtocolResponse {
+                                ok: define_result.is_ok(),
+                                message: define_result.err().unwrap_or_default(),
+                            }))).ok()?;
+                    }
+                    Protocol::ClientRequestCaptureFramebuffer(data) => {
+                        let capture_result = save_framebuffer(&w, &data.name);
+
+                        // Respond with the path of the image
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                            match capture_result {
+                                Ok(path) => ProtocolResponse {
+                                    ok: true,
+                                    message: path.display().to_string(),
+                                },
+                                Err(message) => ProtocolResponse {
+                                    ok: false,
+                                    message,
+                                },
+                            }))).ok()?;
+                    }
+                    Protocol::ClientRequestSetAnalog(data) => {
+                        w.set_analog(data.analog);
+
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::BothResponse(
+                            ProtocolResponse {
+                                ok: true,
+                                message: String::new(),
+                            }))).ok()?;
+                    }
+                    Protocol::ClientRequestListChannels => {
+                        clients.lock().ok()?[&client_id].1.send((SERVER_ID.to_string(), Protocol::ServerResponseChannels(w.get_channels()))).ok()?;
+                    }
                     _ => {
                         warn!("received server message from client!");
                     }
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, OnceLock, RwLock};
+
+use cgmath::Zero;
+use serde::{Deserialize, Serialize};
+
+use crate::behavior::{BehaviorKind, BlockBehavior};
+use crate::block::{Block, circuit_voxel, PowerState, Signal, VoxelID};
+use crate::grid::Coord;
+use crate::kinds;
+
+/// Simulation state shared with every block while a tick is being computed
+#[derive(Clone, Debug, Default)]
+pub struct Context
+{
+    /// Tick being computed
+    pub ticks: u32,
+    /// Whether wires carry a signal strength rather than on/off signals (see `Scene::set_analog`)
+    pub analog: bool,
+    /// Signal broadcast on each wireless channel, i.e. the OR of its transmitters' inputs prior to this tick
+    pub channels: HashMap<String, Signal>,
+}
+
+/// Interaction of a user with an interactive block (see `BlockKind::interact`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InteractAction
+{
+    /// Flip a toggle
+    #[serde(rename = "toggle")]
+    Toggle,
+    /// Press a button
+    #[serde(rename = "press")]
+    Press,
+    /// Press a key of a keypad
+    #[serde(rename = "key")]
+    Key(u8),
+    /// Set the value of a toggle or dial
+    #[serde(rename = "set")]
+    Set(Signal),
+    /// Turn a dial up by one
+    #[serde(rename = "increment")]
+    Increment,
+    /// Turn a dial down by one
+    #[serde(rename = "decrement")]
+    Decrement,
+}
+
+/// Declares everything the simulator needs to know about one kind of block
+///
+/// Kinds are registered under the name used by the `block` tag of the protocol (or the `kind` of a custom block),
+/// and every block of that kind is laid out and simulated through its implementation.
+pub trait BlockKind: Send + Sync
+{
+    /// Return the voxels that make up the block
+    ///
+    /// Voxels whose names start with an exclamation (see `circuit_voxel`) are the block's terminals
+    fn structure(&self, block: &Block) -> HashMap<VoxelID, Coord>;
+
+    /// Get the signal driven by each output terminal of the block
+    ///
+    /// Terminals which are not listed here are inputs
+    fn outputs(&self, block: &Block) -> HashMap<VoxelID, Signal>;
+
+    /// Compute the block's new state from the signal present at each input terminal prior to this tick
+    ///
+    /// Conductors (see `conductor_width`) receive the signal of their network instead
+    fn evaluate(&self, block: &mut Block, inputs: &HashMap<VoxelID, Signal>, context: &Context);
+
+    /// Whether the block's outputs are signal strengths, which analog scenes carry along wires as they are
+    ///
+    /// Other powered outputs drive wires at full strength
+    fn analog_outputs(&self, _block: &Block) -> bool {
+        false
+    }
+
+    /// Get the insulation color of a conductor, if it has one
+    ///
+    /// Adjacent conductors of different colors are not connected, while uncolored conductors connect to any color
+    fn conductor_color(&self, _block: &Block) -> Option<u8> {
+        Some(0)
+    }
+
+    /// Get the local directions (unit vectors) in which terminals connect to neighboring blocks
+    ///
+    /// Terminals which are not listed here connect on all six faces.
+    /// Terminals sharing a voxel (e.g. the channels of a crossing) must connect on different faces
+    fn terminal_faces(&self, _block: &Block) -> HashMap<VoxelID, Vec<Coord>> {
+        Default::default()
+    }
+
+    /// Get the width of the network formed by this block, if it is a conductor (i.e. a wire or a bus)
+    ///
+    /// Adjacent conductors of the same width form a single contiguous network
+    fn conductor_width(&self, _block: &Block) -> Option<u8> {
+        None
+    }
+
+    /// Whether the block holds bulk data (e.g. memory contents) which is not sent to clients every tick it changes
+    ///
+    /// Such blocks are only reported as changed by a tick when their outputs change, and only their outputs are sent
+    fn bulk_data(&self, _block: &Block) -> bool {
+        false
+    }
+
+    /// Prepare a block which is about to be placed or have its data replaced by a client (e.g. load external resources)
+    ///
+    /// Returns a message describing the problem if the block cannot be placed
+    fn prepare(&self, _block: &mut Block) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether users can interact with blocks of this kind (see `interact`)
+    fn interactive(&self) -> bool {
+        false
+    }
+
+    /// Get the interaction which would give a block of this kind the given data, if any
+    ///
+    /// Clients can only rewrite the data of a block as one of its interactions would (see `Scene::check_rewrite`)
+    fn rewrite_action(&self, _data: &Block) -> Option<InteractAction> {
+        None
+    }
+
+    /// Apply a user interaction to the block
+    ///
+    /// Returns a message describing the problem if the block does not support the interaction
+    fn interact(&self, _block: &mut Block, _action: &InteractAction) -> Result<(), String> {
+        Err("block is not interactive".to_string())
+    }
+}
+
+/// Stand-in for blocks whose kind is not registered (e.g. a world saved with block definitions that were not loaded)
+///
+/// Keeps the block's data intact while occupying a single inert voxel
+struct UnknownKind;
+
+impl BlockKind for UnknownKind
+{
+    fn structure(&self, _block: &Block) -> HashMap<VoxelID, Coord> {
+        [("unknown".to_string(), Coord::zero())].into()
+    }
+
+    fn outputs(&self, _block: &Block) -> HashMap<VoxelID, Signal> {
+        Default::default()
+    }
+
+    fn evaluate(&self, _block: &mut Block, _inputs: &HashMap<VoxelID, Signal>, _context: &Context) {}
+}
+
+/// Stores all known block kinds by name
+#[derive(Clone, Default)]
+pub struct BlockRegistry
+{
+    kinds: HashMap<String, Arc<dyn BlockKind>>,
+}
+
+impl Debug for BlockRegistry
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.kinds.keys()).finish()
+    }
+}
+
+impl BlockRegistry
+{
+    /// Create a registry containing all of the built-in block kinds
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        kinds::register_builtin(&mut registry);
+        registry
+    }
+
+    /// Register a block kind under the given name
+    ///
+    /// Returns false (leaving the registry unchanged) if the name is already taken
+    pub fn register(&mut self, name: &str, kind: Arc<dyn BlockKind>) -> bool {
+        if self.kinds.contains_key(name) {
+            false
+        } else {
+            self.kinds.insert(name.to_string(), kind);
+            true
+        }
+    }
+
+    /// Register a custom block kind (placed as `Block::Custom`) implemented by the given behavior
+    ///
+    /// Returns false (leaving the registry unchanged) if the name is already taken
+    pub fn register_behavior(&mut self, name: &str, behavior: Arc<dyn BlockBehavior>) -> bool {
+        self.register(name, Arc::new(BehaviorKind(behavior)))
+    }
+
+    /// Get the block kind with the given name
+    pub fn get(&self, name: &str) -> Option<Arc<dyn BlockKind>> {
+        self.kinds.get(name).cloned()
+    }
+}
+
+/// Get the registry used by the simulator (initialized with the built-in block kinds on first use)
+pub fn registry() -> &'static RwLock<BlockRegistry> {
+    static REGISTRY: OnceLock<RwLock<BlockRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(BlockRegistry::builtin()))
+}
+
+/// Get the block kind with the given name, or an inert stand-in if it is not registered
+pub fn get_kind(name: &str) -> Arc<dyn BlockKind> {
+    registry()
+        .read()
+        .unwrap()
+        .get(name)
+        .unwrap_or_else(|| Arc::new(UnknownKind))
+}
+
+/// Get the signal present at the given input terminal
+pub fn input_signal(inputs: &HashMap<VoxelID, Signal>, terminal: &str) -> Signal {
+    inputs.get(&circuit_voxel(terminal)).copied().unwrap_or(0)
+}
+
+/// Get whether the given input terminal is powered (i.e. has a non-zero signal)
+pub fn input_power(inputs: &HashMap<VoxelID, Signal>, terminal: &str) -> PowerState {
+    input_signal(inputs, terminal) != 0
+}
@@ -1,11 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BinaryHeap, BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ops::Rem;
 use std::path::Path;
 use std::process::Command;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::UNIX_EPOCH;
 
 use cgmath::Array;
+use log::warn;
 use petgraph::dot::Dot;
 use petgraph::graph::NodeIndex;
 use petgraph::stable_graph::StableGraph;
@@ -13,38 +15,70 @@ use petgraph::Undirected;
 use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
-use crate::block::{Block, circuit_voxel, is_circuit_voxel, Orient, PowerState, VoxelClock, VoxelID, VoxelPowered};
-use crate::grid::{Coord, Grid, GridData};
+use crate::block::{BLACK, Block, bus_mask, circuit_voxel, is_circuit_voxel, MAX_STRENGTH, Orient, PowerState, Signal, VoxelBus, VoxelClock, VoxelGate, VoxelID, VoxelPowered, VoxelWire, WHITE};
+use crate::behavior::BlockBehavior;
+use crate::framebuffer::{Frame, Framebuffer, MAX_FRAMEBUFFER_SIZE, validate_framebuffer_name};
+use crate::grid::{Coord, Grid};
+use crate::kinds::io::display_pixel_index;
+use crate::registry::{Context, input_signal, InteractAction, registry};
 
 pub type InstanceID = u32;
 
 pub type NodeIDType = u32;
 pub type NodeID = NodeIndex<NodeIDType>;
 
+/// Voxels of a block sharing one location, with the circuit node of each terminal
+pub type SpaceVoxels = (InstanceID, Vec<(VoxelID, Option<NodeID>)>);
+
+/// Version of the scene format written by this server
+///
+/// Scenes without a version were saved before terminals only connected through their declared faces
+/// (see `BlockKind::terminal_faces`) and before orientations were rotations (see `Orient::from_legacy`)
+pub const SCENE_VERSION: u32 = 1;
+
 pub const OFF: PowerState = false;
 pub const ON: PowerState = true;
 
+/// Members of a wireless channel (see `Scene::get_channels`)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Channel
+{
+    pub transmitters: Vec<InstanceID>,
+    pub receivers: Vec<InstanceID>,
+}
+
 /// Scene data storage type for serialization/deserialization
+///
+/// Only the blocks are stored: the space and circuit are rebuilt from the block kinds' layouts when loaded
+/// (worlds saved with a stored space and circuit are still accepted)
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SceneData
 {
+    #[serde(default)]
+    version: u32,
     blocks: HashMap<String, (Coord, Orient, Block)>,
-    circuit: StableGraph<(InstanceID, VoxelID, Coord), PowerState, Undirected, NodeIDType>,
-    space: GridData<(InstanceID, VoxelID, Option<NodeID>)>,
     ticks: u32,
+    #[serde(default)]
+    framebuffers: BTreeMap<String, Framebuffer>,
+    #[serde(default)]
+    analog: bool,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    legacy_faces: BTreeSet<InstanceID>,
 }
 
 impl From<Scene> for SceneData
 {
     fn from(value: Scene) -> Self {
         Self {
+            version: SCENE_VERSION,
             blocks: value.blocks
                 .into_iter()
                 .map(|(i, e)| (i.to_string(), e))
                 .collect(),
-            circuit: value.circuit,
-            space: GridData::from(value.space),
             ticks: value.ticks,
+            framebuffers: value.framebuffers,
+            analog: value.analog,
+            legacy_faces: value.legacy_faces,
         }
     }
 }
@@ -52,15 +86,43 @@ impl From<Scene> for SceneData
 impl From<SceneData> for Scene
 {
     fn from(value: SceneData) -> Self {
-        Self {
-            blocks: value.blocks
-                .into_iter()
-                .map(|(i, e)| (InstanceID::from_str(&i).unwrap(), e))
-                .collect(),
-            circuit: value.circuit,
-            space: Grid::from(value.space),
+        let mut scene = Scene {
             ticks: value.ticks,
+            framebuffers: value.framebuffers,
+            analog: value.analog,
+            ..Default::default()
+        };
+
+        // Add blocks in a consistent order
+        let mut blocks = value.blocks
+            .into_iter()
+            .map(|(i, e)| (InstanceID::from_str(&i).unwrap(), e))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(i, _)| *i);
+
+        // Blocks of scenes saved before directional terminals keep connecting on all faces, so their circuits still work
+        scene.legacy_faces = if value.version < 1 {
+            blocks.iter().map(|(i, _)| *i).collect()
+        } else {
+            value.legacy_faces
+        };
+
+        for (id, (location, mut orientation, block)) in blocks {
+            // Scenes saved before orientations were rotations only stored bare facings, which keep their layout
+            if value.version < 1 && orientation == Orient::new(orientation.facing, 0) {
+                orientation = Orient::from_legacy(orientation.facing);
+            }
+
+            if scene.add_block_with_id(id, block.clone(), location, orientation).is_none() {
+                warn!("dropped block {} ({}) as it overlaps another block", id, block);
+            }
         }
+
+        if value.version < 1 && !scene.legacy_faces.is_empty() {
+            warn!("{} blocks saved before directional terminals keep connecting on all faces until they are moved", scene.legacy_faces.len());
+        }
+
+        scene
     }
 }
 
@@ -72,19 +134,36 @@ fn scene_serialize_test()
     use cgmath::Vector3;
 
     let mut s = Scene::default();
-    s.add_block(Block::ANDGate(VoxelPowered::default()), Vector3::unit_y(), Default::default()).unwrap();
+    s.add_block(Block::ANDGate(VoxelGate::default()), Vector3::unit_y(), Default::default()).unwrap();
 
     let g = SceneData::from(s);
     println!("{}", serde_json::to_string_pretty(&g).unwrap());
 }
 
-#[derive(Clone, Debug, Default)]
+/// Scenes are (de)serialized through `SceneData`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(from = "SceneData", into = "SceneData")]
 pub struct Scene
 {
     blocks: HashMap<InstanceID, (Coord, Orient, Block)>,
     circuit: StableGraph<(InstanceID, VoxelID, Coord), PowerState, Undirected, NodeIDType>,
-    space: Grid<(InstanceID, VoxelID, Option<NodeID>)>,
+    /// Voxels by location (several terminals may share a voxel, see `BlockKind::terminal_faces`)
+    space: Grid<SpaceVoxels>,
     ticks: u32,
+    framebuffers: BTreeMap<String, Framebuffer>,
+    /// Whether wires carry a decaying signal strength (see `MAX_STRENGTH`) rather than on/off signals
+    analog: bool,
+    /// Blocks loaded from a scene saved before directional terminals, whose terminals connect on all faces
+    /// (see `Scene::get_terminal_faces`)
+    legacy_faces: BTreeSet<InstanceID>,
+}
+
+/// Scenes are equal if their blocks are (the tick count is ignored so that idle modules are not reported as changed)
+impl PartialEq for Scene
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.blocks == other.blocks
+    }
 }
 
 impl Scene
@@ -104,11 +183,21 @@ impl Scene
             .expect("failed to save world file")
     }
 
-    /// Get the global location of a voxel
+    /// Register a custom block kind, placed as `Block::Custom` with the given kind name
     ///
-    /// NOTE: Does not handle non-forward orientations
+    /// Block kinds are shared by all scenes and must be registered before blocks of that kind are placed or loaded
+    pub fn register_behavior(name: &str, behavior: impl BlockBehavior + 'static) -> Result<(), String> {
+        if registry().write().unwrap().register_behavior(name, Arc::new(behavior)) {
+            Ok(())
+        } else {
+            Err(format!("block kind \"{}\" is already defined", name))
+        }
+    }
+
+    /// Get the global location of a voxel
     fn get_voxel_location(&self, id: InstanceID, voxel_id: VoxelID) -> Coord {
-        self.blocks[&id].2.get_global_structure(self.blocks[&id].0, self.blocks[&id].1)[&voxel_id]
+        let (location, orientation, block) = &self.blocks[&id];
+        orientation.rotate(block.get_structure()[&voxel_id]) + location
     }
 
     /// Get world tick count
@@ -116,12 +205,24 @@ impl Scene
         self.ticks
     }
 
+    /// Get whether wires carry a decaying signal strength
+    pub fn is_analog(&self) -> bool {
+        self.analog
+    }
+
+    /// Switch wires between carrying a decaying signal strength and on/off signals
+    pub fn set_analog(&mut self, analog: bool) {
+        self.analog = analog;
+    }
+
     /// Get the voxels coordinates (global) associated with the provided block
     fn get_voxel_locations(&self, id: InstanceID) -> Vec<Coord> {
         self.blocks[&id].2
             .get_structure()
-            .iter()
-            .map(|(voxel_id, _)| self.get_voxel_location(id, voxel_id.clone()))
+            .keys()
+            .map(|voxel_id| self.get_voxel_location(id, voxel_id.clone()))
+            .collect::<HashSet<Coord>>()
+            .into_iter()
             .collect()
     }
 
@@ -131,25 +232,78 @@ impl Scene
             .2
             .get_global_circuit_voxels(self.blocks[&id].0, self.blocks[&id].1)
             .iter()
-            .map(|(voxel_id, coord)|
-                (voxel_id.clone(), self.space.get(*coord).expect(&format!("voxel {} does not exist", voxel_id)).2.expect("voxel does not have a circuit node")))
+            .map(|(voxel_id, coord)| {
+                let (_, voxels) = self.space.get(*coord).unwrap_or_else(|| panic!("voxel {} does not exist", voxel_id));
+                let node_id = voxels.iter().find(|e| e.0 == *voxel_id).and_then(|e| e.1).expect("voxel does not have a circuit node");
+                (voxel_id.clone(), node_id)
+            })
             .collect()
     }
 
-    /// Determine if any of the given node's edges are independently powered
-    fn get_edge_independent_power(&self, node_id: NodeID) -> PowerState {
+    /// Combine (bitwise OR) the independent signals of all of the given node's edges
+    fn get_edge_independent_signal(&self, node_id: NodeID) -> Signal {
         self.circuit
             .neighbors(node_id)
-            .any(|e| {
+            .map(|e| {
                 let (id, voxel_id, _) = self.circuit.node_weight(e).unwrap();
-                self.blocks[id].2.get_circuit_voxel_power()[voxel_id].unwrap_or(OFF)
+                self.blocks[id].2.get_circuit_voxel_signal()[voxel_id].unwrap_or(0)
             })
+            .fold(0, |acc, e| acc | e)
+    }
+
+    /// Get the pairs of touching terminals of different blocks which are not connected
+    /// (i.e. through a face that one of them does not connect on, or between insulated conductors)
+    pub fn get_unconnected_contacts(&self) -> Vec<((InstanceID, VoxelID), (InstanceID, VoxelID))> {
+        let mut contacts = vec![];
+
+        for node_id in self.circuit.node_indices() {
+            let (id, voxel_id, _) = self.circuit.node_weight(node_id).unwrap();
+            let location = self.get_voxel_location(*id, voxel_id.clone());
+
+            for (_, (neighbor_id, neighbor_voxels)) in self.space.get_adjacent(location) {
+                if neighbor_id == id {
+                    continue;
+                }
+
+                for (neighbor_voxel_id, neighbor_node_id) in neighbor_voxels {
+                    let Some(neighbor_node_id) = neighbor_node_id else { continue };
+
+                    // Report each pair once
+                    if (id, voxel_id) < (neighbor_id, neighbor_voxel_id) && !self.circuit.contains_edge(node_id, *neighbor_node_id) {
+                        contacts.push(((*id, voxel_id.clone()), (*neighbor_id, neighbor_voxel_id.clone())));
+                    }
+                }
+            }
+        }
+
+        contacts.sort();
+        contacts
+    }
+
+    /// Get the global directions in which the terminals of the block with the given ID connect
+    /// (see `Block::get_global_terminal_faces`)
+    ///
+    /// Terminals of legacy blocks connect on all faces, except for terminals sharing a voxel (e.g. the channels
+    /// of a crossing), which always had faces
+    fn get_terminal_faces(&self, id: InstanceID) -> HashMap<VoxelID, Vec<Coord>> {
+        let (_, orientation, block) = &self.blocks[&id];
+        let faces = block.get_global_terminal_faces(*orientation);
+
+        if !self.legacy_faces.contains(&id) {
+            return faces;
+        }
+
+        let structure = block.get_structure();
+        faces
+            .into_iter()
+            .filter(|(voxel_id, _)| structure.values().filter(|e| **e == structure[voxel_id]).count() > 1)
+            .collect()
     }
 
     /// Save the circuit to a `.dot` file
     ///
-    /// Requires the `dot` program to be in the system `PATH`
-    #[allow(dead_code)]
+    /// Requires the `dot` program to be in the system `PATH` (rendering runs in the background)
+    #[allow(dead_code, clippy::needless_borrows_for_generic_args, clippy::zombie_processes)]
     pub fn save_debug_circuit(&self, path: &Path) {
         let d = Dot::with_config(&self.circuit, &[]);
         std::fs::write(&path, format!("{:?}", d)).unwrap();
@@ -164,9 +318,111 @@ impl Scene
         self.blocks.clone()
     }
 
+    /// Get all blocks and corresponding states
+    pub fn get_blocks(&self) -> &HashMap<InstanceID, (Coord, Orient, Block)> {
+        &self.blocks
+    }
+
+    /// Get the transmitters and receivers of each wireless channel by name
+    pub fn get_channels(&self) -> BTreeMap<String, Channel> {
+        let mut channels = BTreeMap::<String, Channel>::new();
+
+        for (id, (_, _, block)) in &self.blocks {
+            match block {
+                Block::Transmitter(data) => channels.entry(data.channel.clone()).or_default().transmitters.push(*id),
+                Block::Receiver(data) => channels.entry(data.channel.clone()).or_default().receivers.push(*id),
+                _ => {}
+            }
+        }
+
+        for channel in channels.values_mut() {
+            channel.transmitters.sort();
+            channel.receivers.sort();
+        }
+
+        channels
+    }
+
+    /// Copy the blocks lying entirely within the given (inclusive) bounds into a new scene, with `min` as its origin
+    pub fn get_region(&self, min: Coord, max: Coord) -> Scene {
+        let mut region = Scene {
+            ticks: self.ticks,
+            ..Default::default()
+        };
+
+        let mut blocks = self.blocks
+            .iter()
+            .filter(|(_, (location, orientation, block))| block
+                .get_global_structure(*location, *orientation)
+                .values()
+                .all(|e| (0..3).all(|i| min[i] <= e[i] && e[i] <= max[i])))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(i, _)| **i);
+
+        for (id, (location, orientation, block)) in blocks {
+            if self.legacy_faces.contains(id) {
+                region.legacy_faces.insert(*id);
+            }
+            region.add_block_with_id(*id, block.clone(), location - min, *orientation);
+        }
+
+        region
+    }
+
+    /// Define (or redefine) the framebuffer with the given name
+    pub fn define_framebuffer(&mut self, name: &str, framebuffer: Framebuffer) -> Result<(), String> {
+        validate_framebuffer_name(name)?;
+
+        if !(1..=MAX_FRAMEBUFFER_SIZE).contains(&framebuffer.width) || !(1..=MAX_FRAMEBUFFER_SIZE).contains(&framebuffer.height) {
+            return Err(format!("framebuffer must be between 1x1 and {0}x{0} pixels", MAX_FRAMEBUFFER_SIZE));
+        }
+
+        self.framebuffers.insert(name.to_string(), framebuffer);
+        Ok(())
+    }
+
+    /// Remove the framebuffer with the given name
+    pub fn remove_framebuffer(&mut self, name: &str) -> Option<Framebuffer> {
+        self.framebuffers.remove(name)
+    }
+
+    /// Get all framebuffers by name
+    pub fn get_framebuffers(&self) -> &BTreeMap<String, Framebuffer> {
+        &self.framebuffers
+    }
+
+    /// Capture the current image of the framebuffer with the given name
+    pub fn capture_framebuffer(&self, name: &str) -> Option<Frame> {
+        let framebuffer = self.framebuffers.get(name)?;
+
+        let pixels = (0..framebuffer.height)
+            .flat_map(|row| (0..framebuffer.width).map(move |column| framebuffer.location(column, row)))
+            .map(|location| {
+                let Some((id, voxels)) = self.space.get(location) else { return BLACK };
+                let voxel_id = &voxels[0].0;
+
+                match &self.blocks[id].2 {
+                    Block::Pixel(data) => if data.powered { WHITE } else { BLACK },
+                    Block::ColorPixel(data) => data.color,
+                    Block::Display(data) => display_pixel_index(data, voxel_id)
+                        .and_then(|i| data.pixels.get(i).copied())
+                        .unwrap_or(BLACK),
+                    _ => BLACK
+                }
+            })
+            .collect();
+
+        Some(Frame {
+            width: framebuffer.width,
+            height: framebuffer.height,
+            pixels,
+        })
+    }
+
     /// Performs one simulation tick of the circuit
     ///
-    /// Returns all the blocks that changed state
+    /// Returns all the blocks that changed state, except for blocks holding bulk data whose outputs did not change
+    /// (see `BlockKind::bulk_data`)
     pub fn simulate_tick(&mut self) -> Vec<InstanceID> {
         self.ticks += 1;
 
@@ -179,236 +435,67 @@ impl Scene
          4. For each contiguous wire network, consider all associated sources (using the lookup in step 3) to determine the wire state
          5. Update the wires as needed (record changes)
 
+         Buses are treated as wires carrying a multi-bit signal, where a network's signal is the bitwise OR of its sources.
+         Wires and buses only form a network with neighbors of the same width.
+         In analog scenes, the signal of a single-bit wire is a strength which decays by one per wire segment
+         away from its strongest source.
+
          */
 
-        // Get wire circuit blocks
+        // Get wire circuit blocks (wires and buses)
 
         let wire_circuit_blocks = self.blocks
             .iter()
-            .filter_map(|(id, (_, _, e))| matches!(e, Block::Wire(_)).then_some((*id, e.clone())))
+            .filter_map(|(id, (_, _, e))| e.is_conductor().then_some((*id, e.clone())))
             .collect::<HashMap<InstanceID, Block>>();
 
         // Compute non-wire circuit block states
 
         let non_wire_circuit_blocks = self.blocks
             .iter()
-            .filter_map(|(id, (_, _, e))| (e.is_circuit_block() && !matches!(e, Block::Wire(_))).then_some((*id, e.clone())))
+            .filter_map(|(id, (_, _, e))| (e.is_circuit_block() && !e.is_conductor()).then_some((*id, e.clone())))
             .collect::<HashMap<InstanceID, Block>>();
 
-        let non_wire_circuit_blocks_updated = non_wire_circuit_blocks
+        // Get input node signals (circuit voxels which do not drive an output)
+        let non_wire_circuit_blocks_inputs = non_wire_circuit_blocks
             .iter()
             .map(|(id, block)| {
-                let mut result = block.clone();
                 let node_ids = self.get_circuit_nodes(*id);
 
-                match &mut result {
-                    Block::Toggle(_) => {
-                        // Toggle is an external independent source therefore its state can only be modified by the user
-                    }
-                    Block::Pixel(data) => {
-                        // Get named node
-                        let input_node = node_ids[&circuit_voxel("pixel")];
-
-                        // Get input node state
-
-                        let input_state = self.get_edge_independent_power(input_node);
-
-                        // Compute output state
-
-                        data.powered = input_state;
-                    }
-                    Block::ANDGate(data) => {
-                        // Get named nodes
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node states
-
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
-
-                        // Compute output state
-
-                        data.powered = input_a_state && input_b_state;
-                    }
-                    Block::ORGate(data) => {
-                        // Get named nodes
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node states
-
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
-
-                        // Compute output state
-
-                        data.powered = input_a_state || input_b_state;
-                    }
-                    Block::XORGate(data) => {
-                        // Get named nodGrides
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node states
-
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
-
-                        // Compute output state
-
-                        data.powered = input_a_state != input_b_state;
-                    }
-                    Block::NANDGate(data) => {
-                        // Get named nodes
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node states
-
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
-
-                        // Compute output state
-
-                        data.powered = !(input_a_state && input_b_state);
-                    }
-                    Block::NORGate(data) => {
-                        // Get named nodes
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node states
-
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
-
-                        // Compute output state
-
-                        data.powered = !(input_a_state || input_b_state);
-                    }
-                    Block::XNORGate(data) => {
-                        // Get named nodes
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node states
-
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
-
-                        // Compute output state
-
-                        data.powered = input_a_state == input_b_state;
-                    }
-                    Block::NOTGate(data) => {
-                        // Get named nodes
-                        let (input_node, _output_node) =
-                            (node_ids[&circuit_voxel("in")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node state
-
-                        let input_state = self.get_edge_independent_power(input_node);
-
-                        // Compute output state
-
-                        data.powered = !input_state;
-                    }
-                    Block::Clock(data) => {
-                        // Clock is an external independent source and therefore has no circuit inputs,
-                        // but it does have a circuit output value based on the input state
-
-                        // Compute clock state
-
-                        data.powered = if data.start_tick > self.ticks {
-                            false
-                        } else {
-                            (self.ticks - data.start_tick) % data.rate == 0
-                        };
-                    }
-                    Block::Pulse(data) => {
-                        // Pulse is an external independent source and therefore has no circuit inputs,
-                        // but it does have a circuit output value based on the input state
-
-                        // Compute pulse state
-
-                        data.powered = data.start_tick + data.pulse_ticks >= self.ticks;
-                    }
-                    Block::Diode(data) => {
-                        // Get named nodes
-                        let (input_node, _output_node) =
-                            (node_ids[&circuit_voxel("in")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node state
-
-                        let input_state = self.get_edge_independent_power(input_node);
-
-                        // Compute output state
-
-                        data.powered = input_state;
-                    }
-                    Block::ToggleLatch(data) => {
-                        // Get named nodes
-                        let (input_node, _output_node) =
-                            (node_ids[&circuit_voxel("in")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node state
+                let inputs = block.get_circuit_voxel_signal()
+                    .into_iter()
+                    .filter(|(_, signal)| signal.is_none())
+                    .map(|(voxel_id, _)| {
+                        let signal = self.get_edge_independent_signal(node_ids[&voxel_id]);
+                        (voxel_id, signal)
+                    })
+                    .collect::<HashMap<VoxelID, Signal>>();
 
-                        let input_state = self.get_edge_independent_power(input_node);
-
-                        // Compute output state
-
-                        data.stored = if input_state && !data.powered {
-                            !data.stored
-                        } else {
-                            data.stored
-                        };
-                        data.powered = input_state;
-                    }
-                    Block::PulseLatch(data) => {
-                        // Get named nodes
-                        let (input_node, _output_node) =
-                            (node_ids[&circuit_voxel("in")], node_ids[&circuit_voxel("out")]);
-
-                        // Get input node state
-
-                        let input_state = self.get_edge_independent_power(input_node);
-
-                        // Compute output state
-
-                        data.pulse_battery = data.pulse_battery.checked_sub(1).unwrap_or(0) +
-                            if input_state {
-                                data.pulse_ticks
-                            } else {
-                                0
-                            };
-                        data.powered = data.pulse_battery > 0;
-                    }
-                    Block::MemoryLatch(data) => {
-                        // Get named nodes
-                        let (input_a_node, input_b_node, _output_node) =
-                            (node_ids[&circuit_voxel("in_a")], node_ids[&circuit_voxel("in_b")], node_ids[&circuit_voxel("out")]);
+                (*id, inputs)
+            })
+            .collect::<HashMap<InstanceID, HashMap<VoxelID, Signal>>>();
 
-                        // Get input node states
+        // Receivers see their transmitters' inputs prior to this tick, i.e. with the same delay as through a gate
+        let mut channels = HashMap::<String, Signal>::new();
+        for (id, block) in &non_wire_circuit_blocks {
+            if let Block::Transmitter(data) = block {
+                *channels.entry(data.channel.clone()).or_default() |= input_signal(&non_wire_circuit_blocks_inputs[id], "in");
+            }
+        }
 
-                        let input_a_state = self.get_edge_independent_power(input_a_node);
-                        let input_b_state = self.get_edge_independent_power(input_b_node);
+        let context = Context {
+            ticks: self.ticks,
+            analog: self.analog,
+            channels,
+        };
 
-                        // Compute output state
+        let non_wire_circuit_blocks_updated = non_wire_circuit_blocks
+            .iter()
+            .map(|(id, block)| {
+                let mut result = block.clone();
 
-                        data.stored = if input_a_state == input_b_state {
-                            data.stored
-                        } else {
-                            if input_a_state {
-                                ON
-                            } else {
-                                OFF
-                            }
-                        };
-                        data.powered = data.stored;
-                    }
-                    _ => panic!("not possible, encountered non-circuit or wire block")
-                }
+                // Compute new state
+                block.kind().evaluate(&mut result, &non_wire_circuit_blocks_inputs[id], &context);
 
                 (*id, result)
             })
@@ -416,7 +503,7 @@ impl Scene
 
         let non_wire_circuit_blocks_delta = non_wire_circuit_blocks_updated
             .iter()
-            .filter(|(id, block)| non_wire_circuit_blocks[id] != *block.clone())
+            .filter(|(id, block)| non_wire_circuit_blocks[id] != **block)
             .map(|e| (*e.0, e.1.clone()))
             .collect::<HashMap<InstanceID, Block>>();
 
@@ -424,7 +511,7 @@ impl Scene
 
         let mut unvisited = wire_circuit_blocks
             .keys()
-            .map(|e| self.get_circuit_nodes(*e)[&circuit_voxel("wire")])
+            .flat_map(|e| self.get_circuit_nodes(*e).into_values())
             .collect::<HashSet<NodeID>>();
         let mut contiguous_wire_networks = Vec::<HashSet<(InstanceID, NodeID)>>::new();
 
@@ -435,10 +522,12 @@ impl Scene
             circuit: &StableGraph<(InstanceID, VoxelID, Coord), PowerState, Undirected, NodeIDType>,
             visited: &mut HashSet<NodeID>)
         {
+            let width = blocks[&circuit.node_weight(root).unwrap().0].2.get_conductor_width();
+
             let neighbor_wires = circuit
                 .neighbors(root)
                 .filter(|e| !visited.contains(e))
-                .filter(|e| matches!(blocks[&circuit.node_weight(*e).unwrap().0].2, Block::Wire(_)))
+                .filter(|e| blocks[&circuit.node_weight(*e).unwrap().0].2.get_conductor_width() == width)
                 .collect::<HashSet<NodeID>>();
 
             *visited = visited.union(&neighbor_wires).copied().collect();
@@ -449,72 +538,98 @@ impl Scene
         }
 
         // Wire network search loop
-        loop {
-            if let Some(node_id) = unvisited.iter().next().copied() {
-                let mut current_set = HashSet::new();
-                current_set.insert(node_id);
-                bfs_wires(node_id, &self.blocks, &self.circuit, &mut current_set);
+        while let Some(node_id) = unvisited.iter().next().copied() {
+            let mut current_set = HashSet::new();
+            current_set.insert(node_id);
+            bfs_wires(node_id, &self.blocks, &self.circuit, &mut current_set);
 
-                contiguous_wire_networks.push(current_set
-                    .iter()
-                    .map(|e| (self.circuit.node_weight(*e).unwrap().0, *e))
-                    .collect());
+            contiguous_wire_networks.push(current_set
+                .iter()
+                .map(|e| (self.circuit.node_weight(*e).unwrap().0, *e))
+                .collect());
 
-                unvisited = unvisited.difference(&current_set).copied().collect();
-            } else {
-                break;
-            }
+            unvisited = unvisited.difference(&current_set).copied().collect();
         }
 
         // Compute new wire states
 
-        // Determine wire networks' state prior to this tick's updates
-        // This can be done by referencing `self.*` since `self` has not been modified yet
-        let contiguous_wire_networks_original_state = contiguous_wire_networks
-            .iter()
-            .map(|e| e.iter().any(|(id, _)| {
-                if let Block::Wire(v) = &self.blocks[id].2 {
-                    v.powered
-                } else {
-                    panic!("not possible, encountered non-wire block");
-                }
-            }))
-            .collect::<Vec<PowerState>>();
+        // Determine the signal each non-wire circuit block drives into each wire node (after this tick's updates),
+        // i.e. powered from outside the node's wire network
+        // Same as `self.get_edge_independent_signal()` but uses the updated circuit block state
+        // Only single-bit networks of analog scenes carry a strength (see `MAX_STRENGTH`), others the raw signal
+        let injected_signal = |node_id: NodeID, strength: bool| -> Signal {
+            self.circuit
+                .neighbors(node_id)
+                .filter(|e| non_wire_circuit_blocks_updated.contains_key(&self.circuit.node_weight(*e).unwrap().0))
+                .map(|e| {
+                    let (id, voxel_id, _) = self.circuit.node_weight(e).unwrap();
+                    let block = &non_wire_circuit_blocks_updated[id];
+                    let signal = block.get_circuit_voxel_signal()[voxel_id].unwrap_or(0);
+
+                    // Analog wires are driven at full strength unless the block outputs a strength
+                    match (strength, block.kind().analog_outputs(block)) {
+                        (false, _) => signal,
+                        (true, true) => signal.min(MAX_STRENGTH),
+                        (true, false) => if signal != 0 { MAX_STRENGTH } else { 0 },
+                    }
+                })
+                .fold(0, |acc, e| if strength { acc.max(e) } else { acc | e })
+        };
+
+        // Determine each wire node's signal (after this tick's non-wire circuit block updates)
+        let mut wire_node_signals = HashMap::<NodeID, Signal>::new();
+        for wire_network in &contiguous_wire_networks {
+            // All wires of a network share the same width
+            let (id, _) = wire_network.iter().next().unwrap();
+            let width = self.blocks[id].2.get_conductor_width().unwrap();
+
+            if self.analog && width == 1 {
+                // Strength decays by one for each wire segment away from the strongest source
+                let nodes = wire_network.iter().map(|e| e.1).collect::<HashSet<NodeID>>();
+                let mut strengths = nodes
+                    .iter()
+                    .map(|e| (*e, injected_signal(*e, true)))
+                    .collect::<HashMap<NodeID, Signal>>();
+                let mut queue = strengths
+                    .iter()
+                    .filter(|e| *e.1 > 1)
+                    .map(|(node_id, strength)| (*strength, *node_id))
+                    .collect::<BinaryHeap<(Signal, NodeID)>>();
 
-        // Determine wire networks' state (after this tick's non-wire circuit block updates)
-        let contiguous_wire_networks_updated_state = contiguous_wire_networks
-            .iter()
-            .map(|wire_network| {
-                for (_, node_id) in wire_network {
-                    // Check if this node's non-wire neighbors are independently powered (i.e. powered from outside this wire network)
-                    // Same as `self.get_edge_independent_power()` but uses the updated circuit block state
-                    if self.circuit
-                        .neighbors(*node_id)
-                        .filter(|e| non_wire_circuit_blocks_updated.contains_key(&self.circuit.node_weight(*e).unwrap().0))
-                        .any(|e| {
-                            let (id, voxel_id, _) = self.circuit.node_weight(e).unwrap();
-                            non_wire_circuit_blocks_updated[id].get_circuit_voxel_power()[voxel_id].unwrap_or(OFF)
-                        }) {
-                        return ON;
+                while let Some((strength, node_id)) = queue.pop() {
+                    if strength < strengths[&node_id] {
+                        continue;
+                    }
+
+                    for neighbor in self.circuit.neighbors(node_id).filter(|e| nodes.contains(e)) {
+                        if strengths[&neighbor] < strength - 1 {
+                            strengths.insert(neighbor, strength - 1);
+                            if strength - 1 > 1 {
+                                queue.push((strength - 1, neighbor));
+                            }
+                        }
                     }
                 }
-                OFF
-            })
-            .collect::<Vec<PowerState>>();
 
-        // Compute delta for all wire blocks of the scene together
-        let all_wires_delta_state = contiguous_wire_networks_updated_state
-            .iter()
-            .enumerate()
-            .filter(|(i, e)| contiguous_wire_networks_original_state[*i] != **e)
-            .flat_map(|(i, e)| contiguous_wire_networks[i].iter().map(|v| (v.0, *e)))
-            .collect::<Vec<(InstanceID, PowerState)>>();
+                wire_node_signals.extend(strengths);
+            } else {
+                let signal = wire_network
+                    .iter()
+                    .fold(0, |acc, (_, node_id)| acc | injected_signal(*node_id, false)) & bus_mask(width);
 
-        // Compute delta for all wire blocks of the scene together
-        let all_wires_delta = all_wires_delta_state
-            .iter()
-            .map(|e| e.0)
-            .collect::<Vec<InstanceID>>();
+                wire_node_signals.extend(wire_network.iter().map(|(_, node_id)| (*node_id, signal)));
+            }
+        }
+
+        // Get the network signal at each terminal of the wire blocks
+        // (a wire block can belong to several networks, e.g. the channels of a crossing)
+        let wire_circuit_blocks_inputs = wire_circuit_blocks
+            .keys()
+            .map(|id| (*id, self.get_circuit_nodes(*id)
+                .into_iter()
+                .map(|(voxel_id, node_id)| (voxel_id, wire_node_signals[&node_id]))
+                .collect::<HashMap<VoxelID, Signal>>()))
+            .collect::<Vec<_>>();
 
         // Update scene state
 
@@ -523,12 +638,14 @@ impl Scene
             self.blocks.get_mut(id).unwrap().2 = block.clone();
         }
 
-        // Wire circuit blocks
-        for (id, power) in &all_wires_delta_state {
-            if let Block::Wire(w) = &mut self.blocks.get_mut(id).unwrap().2 {
-                w.powered = *power;
-            } else {
-                panic!("not possible, encountered non-wire node");
+        // Wire circuit blocks (whose network signals are their only inputs, recording those which changed)
+        let mut all_wires_delta = vec![];
+        for (id, inputs) in &wire_circuit_blocks_inputs {
+            let block = &mut self.blocks.get_mut(id).unwrap().2;
+            block.kind().evaluate(block, inputs, &context);
+
+            if *block != wire_circuit_blocks[id] {
+                all_wires_delta.push(*id);
             }
         }
 
@@ -539,7 +656,7 @@ impl Scene
             let (id_a, id_b) = (self.circuit.node_weight(node_a).unwrap().0, self.circuit.node_weight(node_b).unwrap().0);
             let (block_a, block_b) = (&self.blocks[&id_a].2, &self.blocks[&id_b].2);
 
-            if block_a.get_circuit_power().unwrap_or(OFF) || block_b.get_circuit_power().unwrap_or(OFF) {
+            if block_a.get_circuit_power() || block_b.get_circuit_power() {
                 self.circuit.update_edge(node_a, node_b, ON);
             } else {
                 self.circuit.update_edge(node_a, node_b, OFF);
@@ -548,20 +665,38 @@ impl Scene
 
         // Combine delta IDs and return
 
-        return non_wire_circuit_blocks_delta.keys()
-            .copied()
+        non_wire_circuit_blocks_delta
+            .iter()
+            .filter(|(id, block)| !block.kind().bulk_data(block) || block.kind().outputs(block) != non_wire_circuit_blocks[id].kind().outputs(&non_wire_circuit_blocks[id]))
+            .map(|(id, _)| *id)
             .chain(all_wires_delta.iter().copied())
-            .collect();
+            .collect()
     }
 
     /// Adds the given block at the given location and updates the internal circuit
     ///
     /// Returns the block's ID or `None` if a block overlaps an existing block
     pub fn add_block(&mut self, block: Block, location: Coord, orientation: Orient) -> Option<InstanceID> {
-        let id = UNIX_EPOCH.elapsed().unwrap().as_millis().rem((1u32 << 31u32) as u128) as u32;
+        let mut id = UNIX_EPOCH.elapsed().unwrap().as_millis().rem((1u32 << 31u32) as u128) as u32;
+
+        // Skip IDs already in use (blocks added within the same millisecond)
+        while self.blocks.contains_key(&id) {
+            id = (id + 1).rem(1u32 << 31u32);
+        }
+
         self.add_block_with_id(id, block, location, orientation)
     }
 
+    /// Prepares the given block (see `Block::prepare`) and adds it at the given location
+    ///
+    /// Returns the block's ID or a message describing why it could not be placed
+    pub fn place_block(&mut self, mut block: Block, location: Coord, orientation: Orient) -> Result<InstanceID, String> {
+        block.prepare()?;
+
+        self.add_block(block, location, orientation)
+            .ok_or_else(|| "block overlaps existing block".to_string())
+    }
+
     /// Add block with custom ID (internal use only)
     fn add_block_with_id(&mut self, id: InstanceID, block: Block, location: Coord, orientation: Orient) -> Option<InstanceID> {
         // Check if block overlaps existing block
@@ -581,34 +716,58 @@ impl Scene
         // Filter out non-circuit voxels and add to circuit
         let terminal_node_ids = block.get_structure()
             .iter()
-            .filter(|e| is_circuit_voxel(&e.0))
+            .filter(|e| is_circuit_voxel(e.0))
             .map(|(tid, gc)| (tid.clone(), self.circuit.add_node((id, tid.clone(), *gc))))
             .collect::<HashMap<VoxelID, NodeID>>();
 
-        // Add all voxels (including non-terminals) to grid
+        // Add all voxels (including non-terminals) to grid, grouping the terminals that share a voxel
 
+        let mut voxels = HashMap::<Coord, Vec<(VoxelID, Option<NodeID>)>>::new();
         for (voxel_id, voxel_location) in block.get_global_structure(location, orientation) {
-            self.space.set(voxel_location,
-                           (id, voxel_id.clone(), terminal_node_ids.get(&voxel_id).copied()));
+            let node_id = terminal_node_ids.get(&voxel_id).copied();
+            voxels.entry(voxel_location).or_default().push((voxel_id, node_id));
         }
 
+        for (voxel_location, mut voxel) in voxels {
+            voxel.sort();
+            self.space.set(voxel_location, (id, voxel));
+        }
+
+        /// Check whether a terminal connects in the given (global) direction
+        fn connects(faces: &HashMap<VoxelID, Vec<Coord>>, voxel_id: &VoxelID, direction: Coord) -> bool {
+            faces.get(voxel_id).is_none_or(|e| e.contains(&direction))
+        }
+
+        let faces = self.get_terminal_faces(id);
+
         // Iterate over the circuit voxels (terminals) that make up this block
         for (terminal_voxel_id, terminal_node_id) in terminal_node_ids {
-            let terminal_parent_id = self.circuit.node_weight(terminal_node_id).unwrap().0;
-
-            let terminal_voxel_global_location = self.get_voxel_location(terminal_parent_id, terminal_voxel_id);
+            let terminal_voxel_global_location = self.get_voxel_location(id, terminal_voxel_id.clone());
 
             // Iterate over adjacent voxels
-            for (_, (neighbor_parent_id, neighbor_voxel_id, neighbor_node_id))
+            for (neighbor_location, (neighbor_parent_id, neighbor_voxels))
             in self.space.get_adjacent(terminal_voxel_global_location) {
-                // Skip non-circuit voxels
-                if !is_circuit_voxel(neighbor_voxel_id) {
+                let direction = neighbor_location - terminal_voxel_global_location;
+
+                // Only connect to other blocks, through faces on which both terminals connect
+                if *neighbor_parent_id == id || !connects(&faces, &terminal_voxel_id, direction) {
                     continue;
                 }
 
-                // Add connection to circuit if not part of the same block
-                if *neighbor_parent_id != terminal_parent_id {
-                    self.circuit.update_edge(terminal_node_id, neighbor_node_id.unwrap(), OFF);
+                // Conductors of different colors are insulated from each other
+                let (_, _, neighbor_block) = &self.blocks[neighbor_parent_id];
+                if block.is_conductor() && neighbor_block.is_conductor() &&
+                    matches!((block.get_conductor_color(), neighbor_block.get_conductor_color()), (Some(a), Some(b)) if a != b) {
+                    continue;
+                }
+
+                let neighbor_faces = self.get_terminal_faces(*neighbor_parent_id);
+
+                for (neighbor_voxel_id, neighbor_node_id) in neighbor_voxels {
+                    // Skip non-circuit voxels
+                    if is_circuit_voxel(neighbor_voxel_id) && connects(&neighbor_faces, neighbor_voxel_id, -direction) {
+                        self.circuit.update_edge(terminal_node_id, neighbor_node_id.unwrap(), OFF);
+                    }
                 }
             }
         }
@@ -616,6 +775,31 @@ impl Scene
         Some(id)
     }
 
+    /// Adds copies of all blocks of the given region (e.g. from `get_region`),
+    /// transformed by the given orientation (around the region's origin) and moved to the given location
+    ///
+    /// Returns the new blocks' IDs, or `None` (leaving the scene unchanged) if a block overlaps an existing block
+    pub fn paste_region(&mut self, region: &Scene, location: Coord, orientation: Orient) -> Option<Vec<InstanceID>> {
+        // Add blocks in a consistent order
+        let mut blocks = region.blocks.iter().collect::<Vec<_>>();
+        blocks.sort_by_key(|(i, _)| **i);
+
+        let mut ids = Vec::new();
+        for (_, (block_location, block_orientation, block)) in blocks {
+            match self.add_block(block.clone(), orientation.rotate(*block_location) + location, block_orientation.within(orientation)) {
+                Some(id) => ids.push(id),
+                None => {
+                    for id in ids {
+                        self.remove_block(id);
+                    }
+                    return None;
+                }
+            }
+        }
+
+        Some(ids)
+    }
+
     /// Convenience function used when a block needs to be moved and change state
     pub fn replace_block(&mut self, id: InstanceID, block: Block, location: Coord, orientation: Orient) -> Option<()> {
         self.remove_block(id)?;
@@ -640,6 +824,44 @@ impl Scene
             })
     }
 
+    /// Apply a user interaction to the block with the given ID (see `BlockKind::interact`)
+    ///
+    /// Returns the new block state
+    pub fn interact(&mut self, id: InstanceID, action: &InteractAction) -> Result<Block, String> {
+        let (_, _, block) = self.blocks.get_mut(&id).ok_or_else(|| "block does not exist".to_string())?;
+
+        block.kind().interact(block, action)?;
+        Ok(block.clone())
+    }
+
+    /// Check whether a client may replace the data of the block with the given ID by the given data
+    ///
+    /// Only the state of interactive blocks may be rewritten, to data one of their interactions would give them
+    /// (see `BlockKind::rewrite_action`), and blocks cannot change kind
+    pub fn check_rewrite(&self, id: InstanceID, block: &Block) -> Result<(), String> {
+        let (_, _, current) = self.blocks.get(&id).ok_or_else(|| "block does not exist".to_string())?;
+
+        if current == block {
+            return Ok(());
+        } else if current.kind_name() != block.kind_name() {
+            return Err("blocks cannot change kind (remove and place the block instead)".to_string());
+        } else if !current.kind().interactive() {
+            return Err(format!("the state of {} blocks cannot be rewritten (remove and place the block instead)", current.kind_name()));
+        }
+
+        // The rewrite must be exactly what the interaction does, so that no other field can be set this way
+        let mut interacted = current.clone();
+        let rewritable = current.kind()
+            .rewrite_action(block)
+            .is_some_and(|action| current.kind().interact(&mut interacted, &action).is_ok() && interacted == *block);
+
+        if rewritable {
+            Ok(())
+        } else {
+            Err(format!("only the state changed by interacting with {} blocks can be rewritten (use ClientRequestInteract instead)", current.kind_name()))
+        }
+    }
+
     /// Removes the block with the given ID from all internal data structures
     pub fn remove_block(&mut self, id: InstanceID) -> Option<(Coord, Orient, Block)> {
         if !self.blocks.contains_key(&id) {
@@ -654,6 +876,9 @@ impl Scene
             self.space.remove(location).unwrap();
         }
 
+        // Blocks placed again (e.g. when moved) follow the current rules
+        self.legacy_faces.remove(&id);
+
         self.blocks.remove(&id)
     }
 
@@ -662,6 +887,14 @@ impl Scene
     /// Returns the wire block IDs if the path is valid, `None` if not
     #[allow(dead_code)]
     pub fn add_wire(&mut self, path: Vec<Coord>) -> Option<Vec<InstanceID>> {
+        self.add_wire_with(Block::Wire(Default::default()), path)
+    }
+
+    /// Add a wire (or bus) made of copies of the given block that follows the given path
+    ///
+    /// Returns the block IDs if the path is valid, `None` if not
+    #[allow(dead_code)]
+    pub fn add_wire_with(&mut self, block: Block, path: Vec<Coord>) -> Option<Vec<InstanceID>> {
         /// Check if two integer coordinates are adjacent (Manhattan distance of 1)
         fn is_adjacent(a: Coord, b: Coord) -> bool {
             (a - b).map(|e| e.abs()).sum() == 1
@@ -679,7 +912,7 @@ impl Scene
 
         Some(path.iter()
             .copied()
-            .map(|e| self.add_block(Block::Wire(Default::default()), e, Orient::default()).unwrap())
+            .map(|e| self.add_block(block.clone(), e, Orient::default()).unwrap())
             .collect())
     }
 }
@@ -692,7 +925,7 @@ pub fn scene_solo_test()
 
     let mut scene = Scene::default();
 
-    scene.add_block(Block::Wire(VoxelPowered::default()), Vector3::unit_y(), Default::default()).unwrap();
+    scene.add_block(Block::Wire(VoxelWire::default()), Vector3::unit_y(), Default::default()).unwrap();
 
     let deltas = scene.simulate_tick();
 
@@ -712,42 +945,53 @@ pub fn scene_gate_test()
     let _ = scene.add_wire((0..l).map(|e| Coord::new(1, 0, e)).collect()).unwrap();
     let _ = scene.add_wire((0..l).map(|e| Coord::new(0, 0, l + 3 + e)).collect()).unwrap();
     let _clock_a = scene.add_block(Block::Clock(VoxelClock {
-        rate: 5,
+        high_ticks: 1,
+        low_ticks: 4,
+        phase: 0,
         start_tick: 0,
+        enable: false,
         powered: false,
     }), Coord::new(-1, 0, -1), Default::default()).unwrap();
     let _clock_b = scene.add_block(Block::Clock(VoxelClock {
-        rate: 3,
+        high_ticks: 1,
+        low_ticks: 2,
+        phase: 0,
         start_tick: 0,
+        enable: false,
         powered: false,
     }), Coord::new(1, 0, -1), Default::default()).unwrap();
-    let _gate = scene.add_block(Block::ANDGate(VoxelPowered {
+    let _gate = scene.add_block(Block::ANDGate(VoxelGate {
+        inputs: 2,
         powered: false,
     }), Coord::new(0, 0, l + 1), Default::default()).unwrap();
 
-    scene.save_debug_circuit(&Path::new("./result-initial.dot"));
+    scene.save_debug_circuit(Path::new("./result-initial.dot"));
 
     for i in 1..=20u32 {
         let _deltas = scene.simulate_tick();
 
-        scene.save_debug_circuit(&Path::new(&format!("./result-tick-{}.dot", i)));
+        scene.save_debug_circuit(Path::new(&format!("./result-tick-{}.dot", i)));
     }
 }
 
 #[ignore]
 #[test]
+#[allow(unexpected_cfgs, unused_variables, clippy::needless_borrow)]
 pub fn scene_wire_test()
 {
     let mut scene = Scene::default();
 
     let _ = scene.add_wire((0..10).map(|e| Coord::new(0, 0, e)).collect()).unwrap();
     let _ = scene.add_wire((0..10).map(|e| Coord::new(-5 + e, 1, 0)).collect()).unwrap();
-    let _toggle = scene.add_block(Block::Toggle(VoxelPowered {
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered {
         powered: false,
     }), Coord::new(0, 2, 0), Default::default()).unwrap();
     let _clock = scene.add_block(Block::Clock(VoxelClock {
-        rate: 3,
+        high_ticks: 1,
+        low_ticks: 2,
+        phase: 0,
         start_tick: 2,
+        enable: false,
         powered: false,
     }), Coord::new(-2, 2, 0), Default::default()).unwrap();
 
@@ -764,4 +1008,295 @@ pub fn scene_wire_test()
 
         scene.save_debug_circuit(&Path::new(&format!("./generated/result-tick-{}.dot", i)));
     }
-}
\ No newline at end of file
+}
+#[test]
+pub fn scene_bus_test()
+{
+    let mut scene = Scene::default();
+
+    // Merge two toggles into a 4-bit bus, carry it along two bus segments and split it back out
+    let merger = scene.add_block(Block::BusMerger(VoxelBus { width: 4, value: 0 }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    for x in [0, 4] {
+        scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(x, 0, -1), Default::default()).unwrap();
+    }
+    let bus = scene.add_wire_with(Block::Bus(VoxelBus { width: 4, value: 0 }), vec![Coord::new(0, 0, 2), Coord::new(0, 0, 3)]).unwrap();
+    let splitter = scene.add_block(Block::BusSplitter(VoxelBus { width: 4, value: 0 }), Coord::new(0, 0, 4), Default::default()).unwrap();
+    let bit_2 = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(4, 0, 6), Default::default()).unwrap();
+    let bit_3 = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(6, 0, 6), Default::default()).unwrap();
+
+    // A single-bit wire touching the bus must not join its network
+    let stray = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(1, 0, 3), Default::default()).unwrap();
+
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(merger).unwrap().2, Block::BusMerger(VoxelBus { width: 4, value: 0b0101 }));
+    for id in &bus {
+        assert_eq!(scene.get_block(*id).unwrap().2, Block::Bus(VoxelBus { width: 4, value: 0b0101 }));
+    }
+    assert_eq!(scene.get_block(stray).unwrap().2, Block::Wire(VoxelWire { powered: false, ..Default::default() }));
+
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(splitter).unwrap().2, Block::BusSplitter(VoxelBus { width: 4, value: 0b0101 }));
+    assert_eq!(scene.get_block(bit_2).unwrap().2, Block::Wire(VoxelWire { powered: true, ..Default::default() }));
+    assert_eq!(scene.get_block(bit_3).unwrap().2, Block::Wire(VoxelWire { powered: false, ..Default::default() }));
+}
+
+#[test]
+pub fn scene_crossing_test()
+{
+    use crate::block::{Axis, VoxelCrossing, VoxelWire};
+
+    // Each channel carries the wire along its own axis, whichever way the crossing is oriented
+    for orientation in Orient::all().chain([Orient::RIGHT.with_mirror(Some(Axis::X))]) {
+        let mut scene = Scene::default();
+        let (x, z) = (orientation.rotate(Coord::new(1, 0, 0)), orientation.rotate(Coord::new(0, 0, 1)));
+
+        let crossing = scene.add_block(Block::Crossing(VoxelCrossing::default()), Coord::new(0, 0, 0), orientation).unwrap();
+        scene.add_block(Block::Toggle(VoxelPowered { powered: true }), x * -2, Default::default()).unwrap();
+        scene.add_block(Block::Toggle(VoxelPowered { powered: false }), z * -2, Default::default()).unwrap();
+        let x_wire = [scene.add_wire(vec![x * -1]).unwrap(), scene.add_wire(vec![x, x * 2]).unwrap()].concat();
+        let z_wire = [scene.add_wire(vec![z * -1]).unwrap(), scene.add_wire(vec![z, z * 2]).unwrap()].concat();
+
+        // A wire touching the crossing on a face without a channel stays unconnected
+        let stray = scene.add_block(Block::Wire(VoxelWire::default()), x.cross(z), Default::default()).unwrap();
+
+        scene.simulate_tick();
+        assert_eq!(scene.get_block(crossing).unwrap().2, Block::Crossing(VoxelCrossing { x: true, z: false }));
+        assert!(x_wire.iter().all(|e| scene.get_block(*e).unwrap().2.get_circuit_power()));
+        assert!(z_wire.iter().all(|e| !scene.get_block(*e).unwrap().2.get_circuit_power()));
+        assert!(!scene.get_block(stray).unwrap().2.get_circuit_power());
+
+        // Removing the crossing splits the powered wire
+        scene.remove_block(crossing);
+        scene.simulate_tick();
+        assert!(!scene.get_block(x_wire[2]).unwrap().2.get_circuit_power());
+    }
+}
+
+#[test]
+pub fn scene_wire_color_test()
+{
+    let red = Block::Wire(VoxelWire { color: 1, ..Default::default() });
+    let blue = Block::Wire(VoxelWire { color: 2, ..Default::default() });
+
+    // Two touching parallel wires of different colors, only one of them driven
+    let mut scene = Scene::default();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    let red_wire = scene.add_wire_with(red.clone(), (0..4).map(|e| Coord::new(0, 0, e)).collect()).unwrap();
+    let blue_wire = scene.add_wire_with(blue, (0..4).map(|e| Coord::new(1, 0, e)).collect()).unwrap();
+
+    // Colored wires still connect to gate terminals
+    let gate = scene.add_block(Block::NOTGate(VoxelPowered::default()), Coord::new(1, 0, 4), Default::default()).unwrap();
+    let output = scene.add_wire_with(red, vec![Coord::new(1, 0, 6)]).unwrap();
+
+    scene.simulate_tick();
+    scene.simulate_tick();
+    assert!(red_wire.iter().all(|e| scene.get_block(*e).unwrap().2.get_circuit_power()));
+    assert!(blue_wire.iter().all(|e| !scene.get_block(*e).unwrap().2.get_circuit_power()));
+    assert_eq!(scene.get_block(gate).unwrap().2, Block::NOTGate(VoxelPowered { powered: true }));
+    assert!(scene.get_block(output[0]).unwrap().2.get_circuit_power());
+
+    // Crossings carry colored wires across each other
+    let mut scene = Scene::default();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(-2, 0, 0), Default::default()).unwrap();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: false }), Coord::new(0, 0, -2), Default::default()).unwrap();
+    let red_wire = scene.add_wire_with(Block::Wire(VoxelWire { color: 1, ..Default::default() }), vec![Coord::new(-1, 0, 0)]).unwrap();
+    let blue_wire = scene.add_wire_with(Block::Wire(VoxelWire { color: 2, ..Default::default() }), vec![Coord::new(0, 0, -1)]).unwrap();
+    scene.add_block(Block::Crossing(Default::default()), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let red_out = scene.add_wire_with(Block::Wire(VoxelWire { color: 1, ..Default::default() }), vec![Coord::new(1, 0, 0)]).unwrap();
+    let blue_out = scene.add_wire_with(Block::Wire(VoxelWire { color: 2, ..Default::default() }), vec![Coord::new(0, 0, 1)]).unwrap();
+
+    scene.simulate_tick();
+    scene.simulate_tick();
+    assert!(scene.get_block(red_wire[0]).unwrap().2.get_circuit_power());
+    assert!(scene.get_block(red_out[0]).unwrap().2.get_circuit_power());
+    assert!(!scene.get_block(blue_wire[0]).unwrap().2.get_circuit_power());
+    assert!(!scene.get_block(blue_out[0]).unwrap().2.get_circuit_power());
+
+    // Wires saved before colors existed are uncolored
+    let wire: Block = serde_json::from_str(r#"{"block": "wire", "data": {"powered": true}}"#).unwrap();
+    assert_eq!(wire, Block::Wire(VoxelWire { powered: true, color: 0, strength: 0 }));
+}
+
+#[test]
+pub fn scene_terminal_faces_test()
+{
+    // A NOT gate with a wire touching its output from the side and a toggle touching its input from the side
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(1, 0, 0), Default::default()).unwrap();
+    let gate = scene.add_block(Block::NOTGate(VoxelPowered::default()), Coord::new(0, 0, 0), Default::default()).unwrap();
+    let side = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(-1, 0, 1), Default::default()).unwrap();
+    let front = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(0, 0, 2), Default::default()).unwrap();
+
+    scene.simulate_tick();
+    scene.simulate_tick();
+    assert!(scene.get_block(front).unwrap().2.get_circuit_power());
+    assert!(!scene.get_block(side).unwrap().2.get_circuit_power());
+    assert_eq!(scene.get_unconnected_contacts().len(), 2);
+
+    // Faces turn with the block
+    let mut turned = Scene::default();
+    let gate_turned = turned.add_block(Block::NOTGate(VoxelPowered::default()), Coord::new(0, 0, 0), Orient::RIGHT).unwrap();
+    let output = turned.get_voxel_location(gate_turned, circuit_voxel("out"));
+    let facing = turned.add_block(Block::Wire(VoxelWire::default()), output * 2, Default::default()).unwrap();
+    turned.simulate_tick();
+    turned.simulate_tick();
+    assert!(turned.get_block(facing).unwrap().2.get_circuit_power());
+
+    // Blocks of scenes saved before directional terminals keep connecting on all faces,
+    // including once saved again with the current version
+    let mut data = serde_json::to_value(SceneData::from(scene)).unwrap();
+    data.as_object_mut().unwrap().remove("version");
+    let loaded = Scene::from(serde_json::from_value::<SceneData>(data).unwrap());
+    assert!(loaded.get_unconnected_contacts().is_empty());
+
+    let data = serde_json::to_value(SceneData::from(loaded)).unwrap();
+    assert_eq!(data["version"], SCENE_VERSION);
+    let mut loaded = Scene::from(serde_json::from_value::<SceneData>(data).unwrap());
+    assert!(loaded.get_unconnected_contacts().is_empty());
+
+    // The legacy circuit still works: the toggle drives the gate, which drives both wires
+    loaded.simulate_tick();
+    loaded.simulate_tick();
+    assert!(!loaded.get_block(side).unwrap().2.get_circuit_power());
+    loaded.update_block(toggle, Block::Toggle(VoxelPowered { powered: false }));
+    loaded.simulate_tick();
+    loaded.simulate_tick();
+    assert!(loaded.get_block(side).unwrap().2.get_circuit_power());
+    assert!(loaded.get_block(front).unwrap().2.get_circuit_power());
+
+    // Moved blocks follow the current rules
+    let (location, orientation, block) = loaded.get_block(gate).unwrap();
+    loaded.replace_block(gate, block, location, orientation).unwrap();
+    assert_eq!(loaded.get_unconnected_contacts().len(), 2);
+    assert!(loaded.get_unconnected_contacts().iter().all(|e| e.0.0 == gate || e.1.0 == gate));
+}
+
+#[test]
+pub fn scene_analog_test()
+{
+    use crate::block::VoxelAmplifier;
+
+    let strength = |scene: &Scene, id: InstanceID| match scene.get_block(id).unwrap().2 {
+        Block::Wire(data) => data.strength,
+        _ => unreachable!(),
+    };
+
+    let mut scene = Scene::default();
+    scene.set_analog(true);
+
+    // A long wire only carries the signal of a toggle for 15 segments
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    let long = scene.add_wire((0..20).map(|e| Coord::new(0, 0, e)).collect()).unwrap();
+
+    // An amplifier boosts a decayed signal
+    scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(5, 0, -1), Default::default()).unwrap();
+    scene.add_wire((0..10).map(|e| Coord::new(5, 0, e)).collect()).unwrap();
+    scene.add_block(Block::Amplifier(VoxelAmplifier { gain: 2, value: 0 }), Coord::new(5, 0, 10), Default::default()).unwrap();
+    let boosted = scene.add_wire((12..15).map(|e| Coord::new(5, 0, e)).collect()).unwrap();
+
+    scene.simulate_tick();
+    scene.simulate_tick();
+    assert_eq!(long.iter().map(|e| strength(&scene, *e)).collect::<Vec<_>>(), [15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 0, 0, 0]);
+    assert!(scene.get_block(long[14]).unwrap().2.get_circuit_power());
+    assert!(!scene.get_block(long[15]).unwrap().2.get_circuit_power());
+    assert_eq!(boosted.iter().map(|e| strength(&scene, *e)).collect::<Vec<_>>(), [12, 11, 10]);
+
+    // The mode is saved with the world, and digital wires are powered along their whole length
+    let mut loaded = Scene::from(serde_json::from_str::<SceneData>(&serde_json::to_string(&SceneData::from(scene)).unwrap()).unwrap());
+    assert!(loaded.is_analog());
+    loaded.set_analog(false);
+    loaded.simulate_tick();
+    assert!(long.iter().all(|e| loaded.get_block(*e).unwrap().2 == Block::Wire(VoxelWire { powered: true, ..Default::default() })));
+
+    // Buses carry their exact value in analog worlds
+    let mut scene = Scene::default();
+    scene.set_analog(true);
+    let merger = scene.add_block(Block::BusMerger(VoxelBus { width: 4, value: 0 }), Coord::new(0, 0, 0), Default::default()).unwrap();
+    for x in [0, 4] {
+        scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(x, 0, -1), Default::default()).unwrap();
+    }
+    let bus = scene.add_wire_with(Block::Bus(VoxelBus { width: 4, value: 0 }), vec![Coord::new(0, 0, 2), Coord::new(0, 0, 3)]).unwrap();
+
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(merger).unwrap().2, Block::BusMerger(VoxelBus { width: 4, value: 0b0101 }));
+    for id in &bus {
+        assert_eq!(scene.get_block(*id).unwrap().2, Block::Bus(VoxelBus { width: 4, value: 0b0101 }));
+    }
+}
+
+#[test]
+pub fn scene_channel_test()
+{
+    use crate::block::VoxelChannel;
+
+    let channel = |name: &str| VoxelChannel { channel: name.to_string(), ..Default::default() };
+
+    // Two transmitters on channel "a" (only one of them driven), a receiver on "a" and one on "b"
+    let mut scene = Scene::default();
+    let toggle = scene.add_block(Block::Toggle(VoxelPowered { powered: true }), Coord::new(0, 0, -1), Default::default()).unwrap();
+    let transmitter = scene.place_block(Block::Transmitter(channel("a")), Coord::new(0, 0, 0), Default::default()).unwrap();
+    scene.add_block(Block::Toggle(VoxelPowered { powered: false }), Coord::new(10, 0, -1), Default::default()).unwrap();
+    let idle = scene.place_block(Block::Transmitter(channel("a")), Coord::new(10, 0, 0), Default::default()).unwrap();
+    let receiver = scene.place_block(Block::Receiver(channel("a")), Coord::new(20, 0, 0), Default::default()).unwrap();
+    let other = scene.place_block(Block::Receiver(channel("b")), Coord::new(30, 0, 0), Default::default()).unwrap();
+    let wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(20, 0, 1), Default::default()).unwrap();
+    let other_wire = scene.add_block(Block::Wire(VoxelWire::default()), Coord::new(30, 0, 1), Default::default()).unwrap();
+
+    assert!(scene.place_block(Block::Receiver(channel(" ")), Coord::new(40, 0, 0), Default::default()).is_err());
+
+    let mut transmitters = vec![transmitter, idle];
+    transmitters.sort();
+    assert_eq!(scene.get_channels(), BTreeMap::from([
+        ("a".to_string(), Channel { transmitters, receivers: vec![receiver] }),
+        ("b".to_string(), Channel { transmitters: vec![], receivers: vec![other] }),
+    ]));
+
+    // Receivers take one tick to follow their transmitters, like a gate
+    scene.simulate_tick();
+    assert_eq!(scene.get_block(receiver).unwrap().2, Block::Receiver(VoxelChannel { channel: "a".to_string(), signal: 1 }));
+    assert!(scene.get_block(wire).unwrap().2.get_circuit_power());
+    assert!(!scene.get_block(other_wire).unwrap().2.get_circuit_power());
+
+    scene.update_block(toggle, Block::Toggle(VoxelPowered { powered: false }));
+    scene.simulate_tick();
+    assert!(!scene.get_block(wire).unwrap().2.get_circuit_power());
+}
+
+#[test]
+pub fn scene_legacy_orientation_test()
+{
+    use crate::block::Facing;
+
+    // Facings of unversioned scenes turned blocks by swapping two axes
+    let legacy = |facing: Facing, e: Coord| match facing {
+        Facing::FORWARD => e,
+        Facing::RIGHT => Coord::new(e.z, e.y, e.x),
+        Facing::LEFT => Coord::new(-e.z, e.y, -e.x),
+        Facing::BACKWARD => Coord::new(-e.x, e.y, -e.z),
+        Facing::UPWARD => Coord::new(e.x, e.z, e.y),
+        Facing::DOWNWARD => Coord::new(e.x, -e.z, -e.y),
+    };
+
+    let gate = Block::ANDGate(VoxelGate::default());
+    let position = Coord::new(4, -2, 7);
+
+    for facing in Facing::ALL {
+        let data = SceneData {
+            version: 0,
+            blocks: [("1".to_string(), (position, Orient::new(facing, 0), gate.clone()))].into(),
+            ..Default::default()
+        };
+        let scene = Scene::from(data);
+
+        // Loaded blocks keep their layout, and keep it once saved in the current format
+        let saved = Scene::from(serde_json::from_str::<SceneData>(&serde_json::to_string(&SceneData::from(scene.clone())).unwrap()).unwrap());
+        for scene in [scene, saved] {
+            let (_, orientation, block) = scene.get_block(1).unwrap();
+            for (voxel_id, local) in block.get_structure() {
+                assert_eq!(scene.get_voxel_location(1, voxel_id.clone()), legacy(facing, local) + position,
+                           "{} moved when loading a block facing {:?}", voxel_id, facing);
+            }
+            assert_eq!(orientation, Orient::from_legacy(facing));
+        }
+    }
+}